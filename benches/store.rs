@@ -0,0 +1,141 @@
+//! Throughput of the core store operations against the in-memory
+//! engine, with session data of varied sizes. Run with `cargo bench`.
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use surrealdb::{engine::local::Db, Surreal};
+use time::OffsetDateTime;
+use tokio::runtime::Runtime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    ExpiredDeletion, SessionStore,
+};
+use tower_sessions_surrealdb_store::SurrealSessionStore;
+
+/// Number of keys in the session data, from a typical login session to
+/// a large map dominated by serialization.
+const PAYLOAD_KEYS: [usize; 3] = [1, 100, 10_000];
+
+/// Number of expired sessions deleted per cleanup.
+const EXPIRED_SESSIONS: usize = 100;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Error building runtime")
+}
+
+async fn new_store() -> SurrealSessionStore<Db> {
+    let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+        .await
+        .expect("Surreal initialization failure");
+    db.use_ns("bench")
+        .use_db("bench")
+        .await
+        .expect("Surreal namespace initialization failure");
+    SurrealSessionStore::new(db, "sessions".to_string())
+}
+
+fn make_record(keys: usize, expires_in: time::Duration) -> Record {
+    let data: HashMap<String, serde_json::Value> = (0..keys)
+        .map(|i| (format!("key_{i}"), serde_json::json!(format!("value_{i}"))))
+        .collect();
+    Record {
+        id: Id::default(),
+        data,
+        expiry_date: OffsetDateTime::now_utc() + expires_in,
+    }
+}
+
+fn save(c: &mut Criterion) {
+    let runtime = runtime();
+    let store = runtime.block_on(new_store());
+    let mut group = c.benchmark_group("save");
+    group.throughput(Throughput::Elements(1));
+    for keys in PAYLOAD_KEYS {
+        let session = make_record(keys, time::Duration::days(1));
+        group.bench_with_input(BenchmarkId::from_parameter(keys), &session, |b, session| {
+            b.to_async(&runtime)
+                .iter(|| async { store.save(session).await.expect("Error saving") })
+        });
+    }
+    group.finish();
+}
+
+fn load(c: &mut Criterion) {
+    let runtime = runtime();
+    let store = runtime.block_on(new_store());
+    let mut group = c.benchmark_group("load");
+    group.throughput(Throughput::Elements(1));
+    for keys in PAYLOAD_KEYS {
+        let session = make_record(keys, time::Duration::days(1));
+        runtime
+            .block_on(store.save(&session))
+            .expect("Error saving");
+        group.bench_with_input(BenchmarkId::from_parameter(keys), &session.id, |b, id| {
+            b.to_async(&runtime)
+                .iter(|| async { store.load(id).await.expect("Error loading") })
+        });
+    }
+    group.finish();
+}
+
+fn create(c: &mut Criterion) {
+    let runtime = runtime();
+    let store = runtime.block_on(new_store());
+    let mut group = c.benchmark_group("create");
+    group.throughput(Throughput::Elements(1));
+    for keys in PAYLOAD_KEYS {
+        let session = make_record(keys, time::Duration::days(1));
+        group.bench_with_input(BenchmarkId::from_parameter(keys), &session, |b, session| {
+            b.to_async(&runtime).iter(|| async {
+                let mut session = Record {
+                    id: Id::default(),
+                    ..session.clone()
+                };
+                store.create(&mut session).await.expect("Error creating")
+            })
+        });
+    }
+    group.finish();
+}
+
+fn delete_expired(c: &mut Criterion) {
+    let runtime = runtime();
+    let store = runtime.block_on(new_store());
+    let mut group = c.benchmark_group("delete_expired");
+    group.throughput(Throughput::Elements(EXPIRED_SESSIONS as u64));
+    for keys in PAYLOAD_KEYS {
+        group.bench_function(BenchmarkId::from_parameter(keys), |b| {
+            b.to_async(&runtime).iter_custom(|iterations| {
+                let store = &store;
+                async move {
+                    // Only the cleanup is timed, not writing the sessions
+                    // it deletes.
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iterations {
+                        for _ in 0..EXPIRED_SESSIONS {
+                            let session = make_record(keys, time::Duration::hours(-1));
+                            store.save(&session).await.expect("Error saving");
+                        }
+                        let started = Instant::now();
+                        store
+                            .delete_expired()
+                            .await
+                            .expect("Error deleting expired");
+                        elapsed += started.elapsed();
+                    }
+                    elapsed
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, save, load, create, delete_expired);
+criterion_main!(benches);
@@ -8,9 +8,7 @@ use std::net::SocketAddr;
 use axum::{response::IntoResponse, routing::get, Router};
 use serde::{Deserialize, Serialize};
 use tower::ServiceBuilder;
-use tower_sessions::{
-    cookie::time::Duration, ExpiredDeletion, Expiry, Session, SessionManagerLayer,
-};
+use tower_sessions::{cookie::time::Duration, Expiry, Session, SessionManagerLayer};
 use tower_sessions_surrealdb_store::SurrealSessionStore;
 
 const COUNTER_KEY: &str = "counter";
@@ -31,11 +29,13 @@ async fn main() {
         .expect("Surreal database initialization failure");
 
     // This sets up the store to use the `sessions` table.
-    let session_store = SurrealSessionStore::new(db.clone(), "sessions".to_string());
+    let session_store = SurrealSessionStore::new_default(db.clone());
     let expired_session_cleanup_interval: u64 = 1;
-    tokio::task::spawn(session_store.clone().continuously_delete_expired(
-        tokio::time::Duration::from_secs(60 * expired_session_cleanup_interval),
-    ));
+    let _cleanup = session_store
+        .clone()
+        .spawn_cleanup(tokio::time::Duration::from_secs(
+            60 * expired_session_cleanup_interval,
+        ));
 
     let session_service = ServiceBuilder::new().layer(
         SessionManagerLayer::new(session_store)
@@ -0,0 +1,90 @@
+//! Example demonstrating use of `SurrealSessionStore` with a SurrealDB
+//! server reached over WebSocket, as in a typical deployment. Start a
+//! server first, e.g. with
+//! `surreal start --user root --pass root memory`, then go to the URL
+//! shown in the console in your browser. The counter should increment
+//! on each page load, and after 10 seconds of inactivity the counter
+//! should reset.
+//!
+//! The server address and root credentials default to those of the
+//! command above and can be changed with `SURREALDB_ADDRESS`,
+//! `SURREALDB_USER` and `SURREALDB_PASS`.
+use std::net::SocketAddr;
+
+use axum::{response::IntoResponse, routing::get, Router};
+use serde::{Deserialize, Serialize};
+use surrealdb::{
+    engine::remote::ws::{Client, Ws},
+    opt::auth::Root,
+    Surreal,
+};
+use tower::ServiceBuilder;
+use tower_sessions::{cookie::time::Duration, Expiry, Session, SessionManagerLayer};
+use tower_sessions_surrealdb_store::SurrealSessionStore;
+
+const COUNTER_KEY: &str = "counter";
+
+#[derive(Default, Deserialize, Serialize)]
+struct Counter(usize);
+
+#[tokio::main]
+async fn main() {
+    let address =
+        std::env::var("SURREALDB_ADDRESS").unwrap_or_else(|_| "127.0.0.1:8000".to_string());
+    let username = std::env::var("SURREALDB_USER").unwrap_or_else(|_| "root".to_string());
+    let password = std::env::var("SURREALDB_PASS").unwrap_or_else(|_| "root".to_string());
+
+    let db: Surreal<Client> = Surreal::new::<Ws>(address)
+        .await
+        .expect("Surreal connection failure");
+    db.signin(Root {
+        username: &username,
+        password: &password,
+    })
+    .await
+    .expect("Surreal sign in failure");
+    db.use_ns("testing")
+        .await
+        .expect("Surreal namespace initialization failure");
+    db.use_db("testing")
+        .await
+        .expect("Surreal database initialization failure");
+
+    // This sets up the store to use the `sessions` table, pinging the
+    // server every 30 seconds so an idle connection isn't dropped.
+    let session_store = SurrealSessionStore::new(db, "sessions".to_string())
+        .with_keep_alive(Some(tokio::time::Duration::from_secs(30)));
+    session_store
+        .define_table()
+        .await
+        .expect("Session table definition failure");
+    let expired_session_cleanup_interval: u64 = 1;
+    let _cleanup = session_store
+        .clone()
+        .spawn_cleanup(tokio::time::Duration::from_secs(
+            60 * expired_session_cleanup_interval,
+        ));
+
+    let session_service = ServiceBuilder::new().layer(
+        SessionManagerLayer::new(session_store)
+            .with_secure(false)
+            .with_expiry(Expiry::OnInactivity(Duration::seconds(10))),
+    );
+
+    let app = Router::new()
+        .route("/", get(handler))
+        .layer(session_service);
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
+    println!("Listening on {addr}");
+    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    axum::serve(listener, app.into_make_service())
+        .await
+        .unwrap();
+}
+
+async fn handler(session: Session) -> impl IntoResponse {
+    let counter: Counter = session.get(COUNTER_KEY).await.unwrap().unwrap_or_default();
+    session.insert(COUNTER_KEY, counter.0 + 1).await.unwrap();
+    format!("Current count: {}", counter.0)
+}
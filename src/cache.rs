@@ -0,0 +1,362 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store::Result,
+    ExpiredDeletion, SessionStore,
+};
+
+use crate::SurrealSessionStore;
+
+/// A [`SurrealSessionStore`] keeping recently loaded sessions in a
+/// bounded in-memory cache, so loading a hot session doesn't query the
+/// database every time.
+///
+/// Loads are served from the cache for up to `ttl` after a session was
+/// read from the database, and the least recently used session is
+/// evicted once `capacity` sessions are cached. Saving or deleting a
+/// session through this store removes it from the cache, but changes
+/// made by other stores, e.g. in other processes, are only seen once the
+/// cached copy is older than `ttl`.
+#[derive(Debug, Clone)]
+pub struct CachedSurrealSessionStore<DB: std::fmt::Debug + surrealdb::Connection> {
+    store: SurrealSessionStore<DB>,
+    cache: Arc<Mutex<Cache>>,
+}
+
+#[derive(Debug)]
+struct Cache {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<Id, CacheEntry>,
+    /// Cached ids by the tick they were last used at, oldest first.
+    recency: BTreeMap<u64, Id>,
+    tick: u64,
+    /// Sessions being loaded from the wrapped store.
+    loads: HashMap<Id, Load>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    record: Record,
+    loaded: Instant,
+    used: u64,
+}
+
+/// Loads of a session from the wrapped store in progress, and how many
+/// times the session was saved or deleted since the first of them
+/// started.
+#[derive(Debug, Default)]
+struct Load {
+    generation: u64,
+    count: usize,
+}
+
+/// A load of a session from the wrapped store in progress. Dropping it
+/// caches `record` unless the session was saved or deleted since the
+/// load started, so a load racing with a save doesn't cache the session
+/// the save replaced.
+struct PendingLoad<'a> {
+    cache: &'a Mutex<Cache>,
+    session_id: Id,
+    generation: u64,
+    record: Option<Record>,
+}
+
+impl Drop for PendingLoad<'_> {
+    fn drop(&mut self) {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .finish_load(&self.session_id, self.generation, self.record.take());
+    }
+}
+
+impl Cache {
+    fn get(&mut self, session_id: &Id) -> Option<Record> {
+        let entry = self.entries.get_mut(session_id)?;
+        if entry.loaded.elapsed() >= self.ttl
+            || entry.record.expiry_date <= OffsetDateTime::now_utc()
+        {
+            self.remove(session_id);
+            return None;
+        }
+        self.tick += 1;
+        self.recency.remove(&entry.used);
+        self.recency.insert(self.tick, *session_id);
+        entry.used = self.tick;
+        Some(entry.record.clone())
+    }
+
+    fn insert(&mut self, record: Record) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(&record.id);
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.tick += 1;
+        self.recency.insert(self.tick, record.id);
+        self.entries.insert(
+            record.id,
+            CacheEntry {
+                record,
+                loaded: Instant::now(),
+                used: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, session_id: &Id) {
+        if let Some(entry) = self.entries.remove(session_id) {
+            self.recency.remove(&entry.used);
+        }
+    }
+
+    /// Remove `session_id` and keep loads of it in progress from caching
+    /// what they read before it changed.
+    fn invalidate(&mut self, session_id: &Id) {
+        self.remove(session_id);
+        if let Some(load) = self.loads.get_mut(session_id) {
+            load.generation += 1;
+        }
+    }
+
+    /// Note a load of `session_id` starting, returning the generation to
+    /// finish it with.
+    fn start_load(&mut self, session_id: &Id) -> u64 {
+        let load = self.loads.entry(*session_id).or_default();
+        load.count += 1;
+        load.generation
+    }
+
+    /// Note a load started at `generation` finishing, caching `record` if
+    /// the session wasn't invalidated since.
+    fn finish_load(&mut self, session_id: &Id, generation: u64, record: Option<Record>) {
+        let Some(load) = self.loads.get_mut(session_id) else {
+            return;
+        };
+        let current = load.generation;
+        load.count -= 1;
+        if load.count == 0 {
+            self.loads.remove(session_id);
+        }
+        if let Some(record) = record.filter(|_| current == generation) {
+            self.insert(record);
+        }
+    }
+}
+
+impl<DB: std::fmt::Debug + surrealdb::Connection> CachedSurrealSessionStore<DB> {
+    /// Wrap `store`, caching up to `capacity` sessions for at most `ttl`
+    /// each. A `capacity` of 0 disables caching.
+    pub fn new(store: SurrealSessionStore<DB>, capacity: usize, ttl: Duration) -> Self {
+        Self {
+            store,
+            cache: Arc::new(Mutex::new(Cache {
+                capacity,
+                ttl,
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                tick: 0,
+                loads: HashMap::new(),
+            })),
+        }
+    }
+
+    /// The wrapped store. Sessions saved or deleted through it directly
+    /// are not removed from the cache.
+    pub fn inner(&self) -> &SurrealSessionStore<DB> {
+        &self.store
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl<DB: std::fmt::Debug + surrealdb::Connection> ExpiredDeletion
+    for CachedSurrealSessionStore<DB>
+{
+    async fn delete_expired(&self) -> Result<()> {
+        self.store.delete_expired().await
+    }
+}
+
+#[async_trait]
+impl<DB: std::fmt::Debug + surrealdb::Connection> SessionStore for CachedSurrealSessionStore<DB> {
+    async fn create(&self, session: &mut Record) -> Result<()> {
+        self.store.create(session).await?;
+        self.cache().invalidate(&session.id);
+        Ok(())
+    }
+
+    async fn save(&self, session: &Record) -> Result<()> {
+        self.cache().invalidate(&session.id);
+        let result = self.store.save(session).await;
+        // A load racing with the save may have read the old session.
+        self.cache().invalidate(&session.id);
+        result
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Record>> {
+        let generation = {
+            let mut cache = self.cache();
+            if let Some(record) = cache.get(session_id) {
+                return Ok(Some(record));
+            }
+            cache.start_load(session_id)
+        };
+        let mut pending = PendingLoad {
+            cache: &self.cache,
+            session_id: *session_id,
+            generation,
+            record: None,
+        };
+        let record = self.store.load(session_id).await?;
+        pending.record = record.clone();
+        Ok(record)
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<()> {
+        self.cache().invalidate(session_id);
+        let result = self.store.delete(session_id).await;
+        self.cache().invalidate(session_id);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::{make_record, new_db_connection, save_session, SESSIONS_TABLE};
+
+    #[tokio::test]
+    async fn cached() {
+        let db = new_db_connection().await;
+        let inner = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let store = CachedSurrealSessionStore::new(inner.clone(), 10, Duration::from_secs(60));
+        let session = make_record(None, [("key", "value")].to_vec(), time::Duration::days(1));
+        save_session(&inner, &session).await;
+        assert_eq!(
+            Some(&session),
+            store
+                .load(&session.id)
+                .await
+                .expect("Error loading")
+                .as_ref()
+        );
+
+        inner.delete(&session.id).await.expect("Error deleting");
+        assert_eq!(
+            Some(&session),
+            store
+                .load(&session.id)
+                .await
+                .expect("Error loading")
+                .as_ref(),
+            "Second load should be served from the cache"
+        );
+
+        let updated = make_record(
+            Some(session.id),
+            [("key", "updated")].to_vec(),
+            time::Duration::days(1),
+        );
+        store.save(&updated).await.expect("Error saving");
+        assert_eq!(
+            Some(&updated),
+            store
+                .load(&session.id)
+                .await
+                .expect("Error loading")
+                .as_ref(),
+            "Saving should invalidate the cached session"
+        );
+        store.delete(&session.id).await.expect("Error deleting");
+        assert_eq!(
+            None,
+            store.load(&session.id).await.expect("Error loading"),
+            "Deleting should invalidate the cached session"
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_eviction() {
+        let db = new_db_connection().await;
+        let inner = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let sessions: Vec<Record> = (0..3)
+            .map(|_| make_record(None, [].to_vec(), time::Duration::days(1)))
+            .collect();
+        for session in &sessions {
+            save_session(&inner, session).await;
+        }
+
+        let store = CachedSurrealSessionStore::new(inner.clone(), 2, Duration::from_secs(60));
+        for session in [&sessions[0], &sessions[1], &sessions[0], &sessions[2]] {
+            store.load(&session.id).await.expect("Error loading");
+        }
+        for session in &sessions {
+            inner.delete(&session.id).await.expect("Error deleting");
+        }
+        let cached: Vec<bool> =
+            futures_util::future::join_all(sessions.iter().map(|session| store.load(&session.id)))
+                .await
+                .into_iter()
+                .map(|loaded| loaded.expect("Error loading").is_some())
+                .collect();
+        assert_eq!(
+            vec![true, false, true],
+            cached,
+            "The least recently used session should be evicted"
+        );
+
+        let store = CachedSurrealSessionStore::new(inner.clone(), 2, Duration::ZERO);
+        save_session(&inner, &sessions[0]).await;
+        store.load(&sessions[0].id).await.expect("Error loading");
+        inner.delete(&sessions[0].id).await.expect("Error deleting");
+        assert_eq!(
+            None,
+            store.load(&sessions[0].id).await.expect("Error loading"),
+            "Cached sessions should expire after the TTL"
+        );
+    }
+
+    #[test]
+    fn stale_load() {
+        let mut cache = Cache {
+            capacity: 10,
+            ttl: Duration::from_secs(60),
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            loads: HashMap::new(),
+        };
+        let session = make_record(None, [("key", "value")].to_vec(), time::Duration::days(1));
+
+        let generation = cache.start_load(&session.id);
+        cache.invalidate(&session.id);
+        cache.finish_load(&session.id, generation, Some(session.clone()));
+        assert_eq!(
+            None,
+            cache.get(&session.id),
+            "A load racing with a save should not be cached"
+        );
+        assert!(cache.loads.is_empty(), "Finished loads should be forgotten");
+
+        let generation = cache.start_load(&session.id);
+        cache.finish_load(&session.id, generation, Some(session.clone()));
+        assert_eq!(Some(session), cache.get(&session.id));
+    }
+}
@@ -0,0 +1,56 @@
+//! Pluggable encoding of sessions.
+use tower_sessions_core::{
+    session::Record,
+    session_store::{Error, Result},
+};
+
+use crate::MessagePackEncoding;
+
+/// Encoding of sessions to and from the bytes stored in the database,
+/// see [`with_codec`](crate::SurrealSessionStore::with_codec). Implement
+/// it to store sessions in another format, or to transform the encoded
+/// bytes, e.g. by wrapping [`MessagePackCodec`].
+///
+/// Encoded sessions are still compressed and encrypted by the store if
+/// enabled, so a codec only needs to handle what the store doesn't.
+pub trait SessionCodec: Send + Sync {
+    /// Encode `session`, failing with [`Error::Encode`] if it can't be.
+    fn encode(&self, session: &Record) -> Result<Vec<u8>>;
+
+    /// Decode a session encoded by [`encode`](Self::encode), failing
+    /// with [`Error::Decode`] if it can't be. The expiry date of the
+    /// returned session is replaced by the stored one unless they are in
+    /// the same second, since only the stored one is updated by
+    /// [`touch`](crate::SurrealSessionStore::touch).
+    fn decode(&self, data: &[u8]) -> Result<Record>;
+}
+
+/// The [MessagePack](https://msgpack.org) encoding of the
+/// [`SerializationFormat::MessagePack`](crate::SerializationFormat::MessagePack)
+/// format, which the store uses when no codec is configured.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackCodec {
+    encoding: MessagePackEncoding,
+}
+
+impl MessagePackCodec {
+    /// A codec encoding sessions with `encoding`. Sessions are decoded
+    /// whichever encoding they were encoded with.
+    pub fn new(encoding: MessagePackEncoding) -> Self {
+        Self { encoding }
+    }
+}
+
+impl SessionCodec for MessagePackCodec {
+    fn encode(&self, session: &Record) -> Result<Vec<u8>> {
+        match self.encoding {
+            MessagePackEncoding::Named => rmp_serde::to_vec_named(session),
+            MessagePackEncoding::Positional => rmp_serde::to_vec(session),
+        }
+        .map_err(|e| Error::Encode(e.to_string()))
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Record> {
+        rmp_serde::from_slice(data).map_err(|e| Error::Decode(e.to_string()))
+    }
+}
@@ -0,0 +1,44 @@
+//! Compression of session data, available with the `compression`
+//! feature.
+use std::io::{Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use tower_sessions_core::session_store::{Error, Result};
+
+/// Header marking compressed data. `0xc1` is never used by MessagePack
+/// and can't start JSON, so compressed data is told apart from the
+/// uncompressed encodings stored by earlier versions.
+const HEADER: [u8; 2] = [0xc1, b'z'];
+
+/// Compress `data` with DEFLATE at `level`, from 0 (none) to 9 (best),
+/// prefixed with the compression header.
+pub(crate) fn compress(data: &[u8], level: u32) -> Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(HEADER.to_vec(), Compression::new(level.min(9)));
+    encoder
+        .write_all(data)
+        .map_err(|e| Error::Encode(format!("Failed to compress session: {e}")))?;
+    encoder
+        .finish()
+        .map_err(|e| Error::Encode(format!("Failed to compress session: {e}")))
+}
+
+/// Decompress `data` if it starts with the compression header, or
+/// return `None` if it isn't compressed. Fails if the decompressed data
+/// would be larger than `max_size` bytes.
+pub(crate) fn decompress(data: &[u8], max_size: Option<usize>) -> Result<Option<Vec<u8>>> {
+    let Some(compressed) = data.strip_prefix(&HEADER) else {
+        return Ok(None);
+    };
+    let limit = max_size.map_or(u64::MAX, |max_size| max_size as u64 + 1);
+    let mut decompressed = Vec::new();
+    DeflateDecoder::new(compressed)
+        .take(limit)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| Error::Decode(format!("Failed to decompress session: {e}")))?;
+    match max_size {
+        Some(max_size) if decompressed.len() > max_size => Err(Error::Decode(format!(
+            "Decompressed session data exceeds the limit of {max_size} bytes"
+        ))),
+        _ => Ok(Some(decompressed)),
+    }
+}
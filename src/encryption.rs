@@ -0,0 +1,66 @@
+//! At-rest encryption of session data, available with the `encryption`
+//! feature.
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use tower_sessions_core::session_store::{Error, Result};
+
+/// Length in bytes of the nonce prepended to each ciphertext.
+const NONCE_SIZE: usize = 12;
+
+/// AES-256-GCM cipher encrypting the encoded data of sessions. Each
+/// encryption uses a fresh random nonce, stored in front of the
+/// ciphertext, and authenticates associated data, such as the record
+/// key of the session, which must be given again to decrypt.
+#[derive(Clone)]
+pub(crate) struct Cipher(Aes256Gcm);
+
+impl Cipher {
+    pub(crate) fn new(key: [u8; 32]) -> Self {
+        Self(Aes256Gcm::new(&Key::<Aes256Gcm>::from(key)))
+    }
+
+    pub(crate) fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|e| Error::Encode(format!("Failed to encrypt session: {e}")))?;
+        let mut sealed = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub(crate) fn decrypt(&self, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_SIZE {
+            return Err(Error::Decode(
+                "Encrypted session is shorter than its nonce".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+        self.0
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|e| Error::Decode(format!("Failed to decrypt session: {e}")))
+    }
+}
+
+impl std::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the key.
+        f.write_str("Cipher(..)")
+    }
+}
@@ -0,0 +1,150 @@
+//! The error returned by the store's own methods.
+use std::fmt;
+
+use tower_sessions_core::session_store::Error;
+
+/// Result of the methods of [`SurrealSessionStore`](crate::SurrealSessionStore)
+/// beyond the [`SessionStore`](tower_sessions_core::SessionStore) trait.
+pub type StoreResult<T> = std::result::Result<T, SurrealStoreError>;
+
+/// Error of the methods of [`SurrealSessionStore`](crate::SurrealSessionStore)
+/// beyond the [`SessionStore`](tower_sessions_core::SessionStore) trait,
+/// telling apart the kinds of failure that the stringly
+/// [`Error`](tower_sessions_core::session_store::Error) of the trait
+/// can't. Each variant wraps the trait error it converts into, whose
+/// message it displays. The kind is set where the error is raised, so
+/// it is lost once converted into the trait error.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SurrealStoreError {
+    /// The database couldn't be reached or failed the query, e.g.
+    /// because the connection was lost or the circuit breaker is open.
+    Connection(Error),
+    /// A session couldn't be encoded for or decoded from the database.
+    Serialization(Error),
+    /// Something the operation needs doesn't exist, e.g. a saved
+    /// session that can't be read back or the session table.
+    NotFound(Error),
+    /// The database rejected a record that doesn't match the schema of
+    /// the table, or returned a record of an unexpected shape.
+    SchemaMismatch(Error),
+    /// The operation took longer than the timeout, see
+    /// [`with_timeout`](crate::SurrealSessionStore::with_timeout).
+    Timeout(Error),
+    /// The store is configured in a way the operation doesn't support,
+    /// e.g. with an invalid table name.
+    InvalidConfiguration(Error),
+    /// A session was rejected by the validator, see
+    /// [`with_validator`](crate::SurrealSessionStore::with_validator).
+    Validation(Error),
+    /// The id of a session to create is taken, see
+    /// [`create_strict`](crate::SurrealSessionStore::create_strict).
+    AlreadyExists(Error),
+}
+
+impl SurrealStoreError {
+    /// A [`Connection`](Self::Connection) error with `message`.
+    pub(crate) fn connection(message: impl Into<String>) -> Self {
+        Self::Connection(Error::Backend(message.into()))
+    }
+
+    /// A [`SchemaMismatch`](Self::SchemaMismatch) error with `message`.
+    pub(crate) fn schema_mismatch(message: impl Into<String>) -> Self {
+        Self::SchemaMismatch(Error::Backend(message.into()))
+    }
+
+    /// A [`Timeout`](Self::Timeout) error with `message`.
+    pub(crate) fn timeout(message: impl Into<String>) -> Self {
+        Self::Timeout(Error::Backend(message.into()))
+    }
+
+    /// An [`AlreadyExists`](Self::AlreadyExists) error with `message`.
+    pub(crate) fn already_exists(message: impl Into<String>) -> Self {
+        Self::AlreadyExists(Error::Backend(message.into()))
+    }
+
+    /// An [`InvalidConfiguration`](Self::InvalidConfiguration) error
+    /// with `message`.
+    pub(crate) fn invalid_configuration(message: impl Into<String>) -> Self {
+        Self::InvalidConfiguration(Error::Backend(message.into()))
+    }
+
+    /// A [`NotFound`](Self::NotFound) error with `message`.
+    pub(crate) fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound(Error::Backend(message.into()))
+    }
+
+    /// This error with the message of a backend error replaced by
+    /// `map` of it, keeping its kind.
+    pub(crate) fn map_backend(self, map: impl FnOnce(String) -> String) -> Self {
+        let mapped = |e: Error| match e {
+            Error::Backend(message) => Error::Backend(map(message)),
+            e => e,
+        };
+        match self {
+            Self::Connection(e) => Self::Connection(mapped(e)),
+            Self::Serialization(e) => Self::Serialization(mapped(e)),
+            Self::NotFound(e) => Self::NotFound(mapped(e)),
+            Self::SchemaMismatch(e) => Self::SchemaMismatch(mapped(e)),
+            Self::Timeout(e) => Self::Timeout(mapped(e)),
+            Self::InvalidConfiguration(e) => Self::InvalidConfiguration(mapped(e)),
+            Self::Validation(e) => Self::Validation(mapped(e)),
+            Self::AlreadyExists(e) => Self::AlreadyExists(mapped(e)),
+        }
+    }
+
+    /// The error of the [`SessionStore`](tower_sessions_core::SessionStore)
+    /// trait this error wraps.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Self::Connection(e)
+            | Self::Serialization(e)
+            | Self::NotFound(e)
+            | Self::SchemaMismatch(e)
+            | Self::Timeout(e)
+            | Self::InvalidConfiguration(e)
+            | Self::Validation(e)
+            | Self::AlreadyExists(e) => e,
+        }
+    }
+}
+
+impl fmt::Display for SurrealStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner().fmt(f)
+    }
+}
+
+impl std::error::Error for SurrealStoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.inner())
+    }
+}
+
+/// Wrap an error of the trait, e.g. one returned by a codec, by its
+/// variant alone: backend errors are assumed to come from the database
+/// or the connection to it, and encode and decode errors are
+/// serialization errors.
+impl From<Error> for SurrealStoreError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Backend(_) => Self::Connection(e),
+            Error::Encode(_) | Error::Decode(_) => Self::Serialization(e),
+        }
+    }
+}
+
+impl From<SurrealStoreError> for Error {
+    fn from(e: SurrealStoreError) -> Self {
+        match e {
+            SurrealStoreError::Connection(e)
+            | SurrealStoreError::Serialization(e)
+            | SurrealStoreError::NotFound(e)
+            | SurrealStoreError::SchemaMismatch(e)
+            | SurrealStoreError::Timeout(e)
+            | SurrealStoreError::InvalidConfiguration(e)
+            | SurrealStoreError::Validation(e)
+            | SurrealStoreError::AlreadyExists(e) => e,
+        }
+    }
+}
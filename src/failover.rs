@@ -0,0 +1,295 @@
+use async_trait::async_trait;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store::Result,
+    ExpiredDeletion, SessionStore,
+};
+use tracing::warn;
+
+/// A session store writing to a primary and a secondary store, and
+/// reading from the secondary when the primary fails, so sessions stay
+/// available while e.g. SurrealDB is down for maintenance. The stores
+/// can be any [`SessionStore`], such as a [`SurrealSessionStore`](crate::SurrealSessionStore)
+/// paired with an in-memory or Redis store.
+///
+/// Creates, saves and deletes go to both stores and succeed if either
+/// store succeeds, so the stores drift apart while one of them is down.
+/// Loads are served by the primary, falling back to the secondary if the
+/// primary fails, or if it doesn't have the session and
+/// [`FailoverPolicy::RepairPrimary`] is configured, in which case the
+/// session found is copied to the primary.
+#[derive(Debug, Clone)]
+pub struct FailoverSessionStore<A, B> {
+    primary: A,
+    secondary: B,
+    policy: FailoverPolicy,
+}
+
+/// How a [`FailoverSessionStore`] reconciles its stores after an outage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailoverPolicy {
+    /// The secondary is only read when the primary fails, so sessions
+    /// written during an outage of the primary are lost once it is back.
+    #[default]
+    PrimaryOnly,
+    /// A session the primary doesn't have is looked up in the secondary
+    /// and copied to the primary, so sessions written during an outage
+    /// of the primary are moved to it as they are used. This reads both
+    /// stores on every miss, and a session logged out or deleted while
+    /// the secondary was down comes back this way.
+    RepairPrimary,
+}
+
+impl<A: SessionStore, B: SessionStore> FailoverSessionStore<A, B> {
+    /// Pair `primary` with `secondary`, reconciling them with the
+    /// default [`FailoverPolicy`].
+    pub fn new(primary: A, secondary: B) -> Self {
+        Self {
+            primary,
+            secondary,
+            policy: FailoverPolicy::default(),
+        }
+    }
+
+    /// Reconcile the stores with `policy`.
+    pub fn with_policy(mut self, policy: FailoverPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The primary store.
+    pub fn primary(&self) -> &A {
+        &self.primary
+    }
+
+    /// The secondary store.
+    pub fn secondary(&self) -> &B {
+        &self.secondary
+    }
+}
+
+/// Combine the results of an operation on both stores, succeeding if
+/// either succeeded and failing with the primary's error otherwise.
+fn either(operation: &str, primary: Result<()>, secondary: Result<()>) -> Result<()> {
+    match (primary, secondary) {
+        (Ok(()), Ok(())) => Ok(()),
+        (Ok(()), Err(e)) => {
+            warn!("Secondary session store failed to {operation}: {e}");
+            Ok(())
+        }
+        (Err(e), Ok(())) => {
+            warn!("Primary session store failed to {operation}, used the secondary: {e}");
+            Ok(())
+        }
+        (Err(e), Err(secondary)) => {
+            warn!("Secondary session store failed to {operation}: {secondary}");
+            Err(e)
+        }
+    }
+}
+
+#[async_trait]
+impl<A, B> ExpiredDeletion for FailoverSessionStore<A, B>
+where
+    A: ExpiredDeletion,
+    B: ExpiredDeletion,
+{
+    async fn delete_expired(&self) -> Result<()> {
+        let primary = self.primary.delete_expired().await;
+        let secondary = self.secondary.delete_expired().await;
+        primary.and(secondary)
+    }
+}
+
+#[async_trait]
+impl<A: SessionStore, B: SessionStore> SessionStore for FailoverSessionStore<A, B> {
+    async fn create(&self, session: &mut Record) -> Result<()> {
+        // The primary may move the session to a new id, which the
+        // secondary then stores it under.
+        match self.primary.create(session).await {
+            Ok(()) => {
+                let secondary = self.secondary.save(session).await;
+                either("create", Ok(()), secondary)
+            }
+            Err(e) => {
+                let secondary = self.secondary.create(session).await;
+                either("create", Err(e), secondary)
+            }
+        }
+    }
+
+    async fn save(&self, session: &Record) -> Result<()> {
+        let primary = self.primary.save(session).await;
+        let secondary = self.secondary.save(session).await;
+        either("save", primary, secondary)
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Record>> {
+        match self.primary.load(session_id).await {
+            Ok(Some(session)) => Ok(Some(session)),
+            Ok(None) if self.policy == FailoverPolicy::PrimaryOnly => Ok(None),
+            Ok(None) => {
+                let session = match self.secondary.load(session_id).await {
+                    Ok(session) => session,
+                    Err(e) => {
+                        warn!("Secondary session store failed to load: {e}");
+                        None
+                    }
+                };
+                if let Some(session) = &session {
+                    if let Err(e) = self.primary.save(session).await {
+                        warn!("Failed to copy session to the primary session store: {e}");
+                    }
+                }
+                Ok(session)
+            }
+            Err(e) => {
+                warn!("Primary session store failed to load, using the secondary: {e}");
+                self.secondary.load(session_id).await
+            }
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<()> {
+        let primary = self.primary.delete(session_id).await;
+        let secondary = self.secondary.delete(session_id).await;
+        either("delete", primary, secondary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use tower_sessions_core::session_store::Error;
+
+    use super::*;
+    use crate::{
+        test::{load_session, make_record, new_db_connection, DB},
+        SurrealSessionStore,
+    };
+
+    /// A session store failing every operation while `down` is set.
+    #[derive(Debug, Clone)]
+    struct FlakyStore {
+        store: SurrealSessionStore<DB>,
+        down: Arc<AtomicBool>,
+    }
+
+    impl FlakyStore {
+        fn check(&self) -> Result<()> {
+            if self.down.load(Ordering::SeqCst) {
+                Err(Error::Backend("Store down".to_string()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[async_trait]
+    impl SessionStore for FlakyStore {
+        async fn create(&self, session: &mut Record) -> Result<()> {
+            self.check()?;
+            self.store.create(session).await
+        }
+
+        async fn save(&self, session: &Record) -> Result<()> {
+            self.check()?;
+            self.store.save(session).await
+        }
+
+        async fn load(&self, session_id: &Id) -> Result<Option<Record>> {
+            self.check()?;
+            self.store.load(session_id).await
+        }
+
+        async fn delete(&self, session_id: &Id) -> Result<()> {
+            self.check()?;
+            self.store.delete(session_id).await
+        }
+    }
+
+    #[tokio::test]
+    async fn failover() {
+        let db = new_db_connection().await;
+        let down = Arc::new(AtomicBool::new(false));
+        let primary = FlakyStore {
+            store: SurrealSessionStore::new(db.clone(), "primary_sessions".to_string()),
+            down: down.clone(),
+        };
+        let secondary = SurrealSessionStore::new(db.clone(), "secondary_sessions".to_string());
+        let store = FailoverSessionStore::new(primary.clone(), secondary.clone());
+        let set_down = |value| down.store(value, Ordering::SeqCst);
+
+        let session = make_record(None, [("key", "value")].to_vec(), time::Duration::days(1));
+        store.save(&session).await.expect("Error saving");
+        assert_eq!(
+            Some(session.clone()),
+            load_session(&primary.store, &session).await,
+            "Saved to the primary"
+        );
+        assert_eq!(
+            Some(session.clone()),
+            load_session(&secondary, &session).await,
+            "Saved to the secondary"
+        );
+
+        set_down(true);
+        let during = make_record(None, [("key", "outage")].to_vec(), time::Duration::days(1));
+        store
+            .save(&during)
+            .await
+            .expect("Error saving during outage");
+        assert_eq!(
+            Some(during.clone()),
+            store
+                .load(&during.id)
+                .await
+                .expect("Error loading during outage"),
+            "The secondary serves loads while the primary is down"
+        );
+        assert_eq!(
+            Some(session.clone()),
+            store
+                .load(&session.id)
+                .await
+                .expect("Error loading during outage"),
+            "Sessions saved before the outage are served by the secondary"
+        );
+
+        set_down(false);
+        assert_eq!(None, load_session(&primary.store, &during).await);
+        assert_eq!(
+            None,
+            store.load(&during.id).await.expect("Error loading"),
+            "Only the primary is read while it is up"
+        );
+        let repairing = store.clone().with_policy(FailoverPolicy::RepairPrimary);
+        assert_eq!(
+            Some(during.clone()),
+            repairing.load(&during.id).await.expect("Error loading"),
+            "Sessions missing from the primary are read from the secondary"
+        );
+        assert_eq!(
+            Some(during.clone()),
+            load_session(&primary.store, &during).await,
+            "The primary is repaired"
+        );
+
+        set_down(true);
+        store.delete(&session.id).await.expect("Error deleting");
+        assert_eq!(
+            None,
+            load_session(&secondary, &session).await,
+            "Deleted from the secondary while the primary is down"
+        );
+        let both_down = FailoverSessionStore::new(primary.clone(), primary);
+        assert!(
+            both_down.save(&session).await.is_err(),
+            "Fails when both stores fail"
+        );
+    }
+}
@@ -1,66 +1,957 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use futures_util::{Stream, TryStreamExt};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
 use surrealdb::Surreal;
+use time::OffsetDateTime;
 use tower_sessions_core::{
     session::{Id, Record},
-    session_store::{Error, Result},
+    session_store::{self, Error},
     ExpiredDeletion, SessionStore,
 };
-use tracing::info;
+use tracing::{field, info, info_span, warn, Instrument, Level};
+
+pub use crate::{
+    cache::CachedSurrealSessionStore,
+    codec::{MessagePackCodec, SessionCodec},
+    error::{StoreResult, SurrealStoreError},
+    failover::{FailoverPolicy, FailoverSessionStore},
+    metrics::SessionMetrics,
+};
+
+#[cfg(feature = "otel")]
+pub use crate::otel::OtelMetrics;
+
+mod cache;
+mod codec;
+#[cfg(feature = "compression")]
+mod compression;
+#[cfg(feature = "encryption")]
+mod encryption;
+mod error;
+mod failover;
+mod metrics;
+#[cfg(feature = "otel")]
+mod otel;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 
 #[cfg(all(feature="surrealdb", feature="surrealdb-nightly"))]
 compile_error!{"Features 'surrealdb' and 'surrealdb-nightly' must not be enabled at the same time! See the README for details."}
 
+/// Conventional name of the session table, used by
+/// [`SurrealSessionStore::new_default`].
+pub const DEFAULT_SESSION_TABLE: &str = "sessions";
+
+/// Separator placed between the key prefix and the session id by
+/// default, see [`SurrealSessionStore::with_key_separator`].
+pub const DEFAULT_KEY_SEPARATOR: &str = "_";
+
+/// Error thrown by the query of [`SurrealSessionStore::replace_data`]
+/// when there is no live session to update.
+const SESSION_NOT_UPDATED: &str = "tower-sessions-surrealdb-store: session not updated";
+
+/// Start of the backend error returned when saving a session seemingly
+/// succeeds, but the session can't be read back. Errors the database
+/// returns for the write itself, such as a field of the wrong type in
+/// a schemafull table, are returned instead, prefixed with the record
+/// written.
+pub const SESSION_NOT_SAVED: &str = "Session record not saved";
+
+/// Start of the backend error returned when a store operation takes
+/// longer than the timeout, see [`SurrealSessionStore::with_timeout`].
+pub const SESSION_TIMEOUT: &str = "Session store operation timed out";
+
+/// Start of the backend error returned by
+/// [`SurrealSessionStore::create_strict`] when the id of the session is
+/// taken.
+pub const SESSION_EXISTS: &str = "Session already exists";
+
+/// Start of the encode error returned when a session is rejected by the
+/// validator, see [`SurrealSessionStore::with_validator`].
+pub const SESSION_INVALID: &str = "Session failed validation";
+
+/// Part of the error SurrealDB returns when creating a record that
+/// already exists.
+const RECORD_EXISTS: &str = "already exists";
+
+/// Number of sessions fetched per query by [`SurrealSessionStore::rekey_prefix`].
+const REKEY_BATCH_SIZE: usize = 100;
+
+/// Oldest major version of the SurrealDB server the queries of the store
+/// run on, with either the `surrealdb` or the `surrealdb-nightly`
+/// feature. The clients of both connect to servers from 1.2 on, whose
+/// SurrealQL lacks e.g. `upsert`, so [`SurrealSessionStore::initialize`]
+/// rejects older servers.
+pub const MIN_SURREALDB_MAJOR_VERSION: u64 = 2;
+
+/// Check that `name` can be used as a table or index name in a query
+/// without escaping, i.e. it is non-empty and consists of ASCII
+/// letters, digits and underscores. Needed where SurrealQL doesn't
+/// accept a parameter in place of a name.
+fn validate_identifier(name: &str) -> StoreResult<&str> {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Ok(name)
+    } else {
+        Err(SurrealStoreError::invalid_configuration(format!(
+            "Invalid identifier '{name}': only ASCII letters, digits and underscores are allowed"
+        )))
+    }
+}
+
+/// Check that a server of major version `major` runs the queries of the
+/// store, failing with an error naming `version` otherwise.
+fn check_server_version(major: u64, version: &dyn std::fmt::Display) -> StoreResult<()> {
+    if major >= MIN_SURREALDB_MAJOR_VERSION {
+        Ok(())
+    } else {
+        Err(SurrealStoreError::invalid_configuration(format!(
+            "SurrealDB {version} is not supported, version {MIN_SURREALDB_MAJOR_VERSION}.0 or later is required"
+        )))
+    }
+}
+
+/// Map an error reading a result from the database. Results that
+/// can't be deserialized, e.g. a stored session of the wrong shape, are
+/// decode errors; anything else is a backend error.
+fn read_error(e: surrealdb::Error) -> SurrealStoreError {
+    match e {
+        surrealdb::Error::Db(surrealdb::error::Db::Serialization(message)) => {
+            SurrealStoreError::Serialization(Error::Decode(message))
+        }
+        e => backend_error(e),
+    }
+}
+
+/// Map an error writing a session to the database. Session data that
+/// can't be serialized for the database is an encode error; anything
+/// else is a backend error.
+fn write_error(e: surrealdb::Error) -> SurrealStoreError {
+    match e {
+        surrealdb::Error::Db(surrealdb::error::Db::Serialization(message)) => {
+            SurrealStoreError::Serialization(Error::Encode(message))
+        }
+        e => backend_error(e),
+    }
+}
+
+/// `message` with the id of `session_id` replaced, so that errors of the
+/// database naming a session record don't leak the session id, a
+/// credential, into logs.
+fn redact_id(message: String, session_id: &Id) -> String {
+    message.replace(&session_id.to_string(), "<session id>")
+}
+
+/// Map an error of the database to a backend error, a schema mismatch
+/// if a field of the written record doesn't match its definition.
+fn backend_error(e: surrealdb::Error) -> SurrealStoreError {
+    if is_schema_mismatch(&e) {
+        SurrealStoreError::schema_mismatch(e.to_string())
+    } else {
+        SurrealStoreError::connection(e.to_string())
+    }
+}
+
+/// Whether the database rejected a record that doesn't match the schema
+/// of the table. Remote engines only return the message of the error,
+/// so their schema mismatches are not recognized.
+fn is_schema_mismatch(e: &surrealdb::Error) -> bool {
+    use surrealdb::error::Db;
+    matches!(
+        e,
+        surrealdb::Error::Db(
+            Db::FieldCheck { .. } | Db::FieldValue { .. } | Db::FieldUndefined { .. }
+        )
+    )
+}
+
+/// Whether creating a session failed because its record exists.
+fn is_record_exists(e: &SurrealStoreError) -> bool {
+    matches!(e.inner(), Error::Backend(message) if message.contains(RECORD_EXISTS))
+}
+
+/// Fail with every error in a response to an audited query. When one
+/// statement in a transaction fails the others fail too, so all errors
+/// are reported to surface the one that caused the failure.
+fn audit_errors(mut response: surrealdb::Response) -> StoreResult<surrealdb::Response> {
+    errors_to_result(response.take_errors(), "Audited session write failed")?;
+    Ok(response)
+}
+
+/// Fail with every error in `errors`, in statement order, if any.
+fn errors_to_result(errors: HashMap<usize, surrealdb::Error>, context: &str) -> StoreResult<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let mut errors: Vec<_> = errors.into_iter().collect();
+    errors.sort_by_key(|(index, _)| *index);
+    let schema_mismatch = errors.iter().any(|(_, e)| is_schema_mismatch(e));
+    let errors: Vec<String> = errors.into_iter().map(|(_, e)| e.to_string()).collect();
+    let message = format!("{context}: {}", errors.join("; "));
+    Err(if schema_mismatch {
+        SurrealStoreError::schema_mismatch(message)
+    } else {
+        SurrealStoreError::connection(message)
+    })
+}
+
+/// `period` varied by up to `jitter_fraction` of it either way, picked
+/// by `sample` between 0 and 1.
+fn jittered(period: Duration, jitter_fraction: f64, sample: f64) -> Duration {
+    // Unlike `clamp`, `max` and `min` also map NaN into the range.
+    let jitter = jitter_fraction.max(0.0).min(1.0);
+    period.mul_f64(1.0 - jitter + 2.0 * jitter * sample.max(0.0).min(1.0))
+}
+
+/// Rough size in bytes of the encoded form of a session, cheaper to
+/// compute than the encoding itself.
+fn estimated_size(session: &Record) -> usize {
+    fn value_size(value: &serde_json::Value) -> usize {
+        match value {
+            serde_json::Value::String(s) => s.len(),
+            serde_json::Value::Array(values) => values.iter().map(value_size).sum(),
+            serde_json::Value::Object(map) => {
+                map.iter().map(|(k, v)| k.len() + value_size(v)).sum()
+            }
+            _ => 8,
+        }
+    }
+    session
+        .data
+        .iter()
+        .map(|(k, v)| k.len() + value_size(v))
+        .sum()
+}
+
+/// Default number of sessions fetched per query by scans of the whole
+/// table such as [`SurrealSessionStore::export_stream`], see
+/// [`SurrealSessionStore::with_page_size`].
+pub const EXPORT_PAGE_SIZE: usize = 1000;
+
+/// Default name of the field holding the expiry date of a session, see
+/// [`SurrealSessionStore::with_expiry_column`].
+pub const DEFAULT_EXPIRY_COLUMN: &str = "expiry_date";
+
+/// Default name of the field holding the encoded data of a session, see
+/// [`SurrealSessionStore::with_data_column`].
+pub const DEFAULT_DATA_COLUMN: &str = "data";
+
+/// Default fraction by which
+/// [`SurrealSessionStore::continuously_delete_expired_with_jitter`]
+/// varies the period between cleanups.
+pub const DEFAULT_CLEANUP_JITTER: f64 = 0.1;
+
+/// Name of the field marking a session as pinned, see
+/// [`SurrealSessionStore::save_pinned`].
+pub const PINNED_COLUMN: &str = "pinned";
+
+/// Name of the field holding the user id of a session, see
+/// [`SurrealSessionStore::with_user_id_key`].
+pub const USER_ID_COLUMN: &str = "user_id";
+
 /// Representation of a session in the database.
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+///
+/// The session is encoded as a MessagePack map keyed by field name, so
+/// fields added to [`Record`] by newer versions are skipped when an
+/// older version decodes them. Records encoded positionally by earlier
+/// versions of this crate still decode.
+///
+/// Every kind of [`serde_json::Value`] round trips exactly, including
+/// integers anywhere in the `i64` and `u64` ranges, which MessagePack
+/// encodes natively, and floats, which are encoded as 64-bit floats so
+/// are never confused with integers. Values `serde_json` itself cannot
+/// represent, such as NaN or integers beyond `u64`, cannot be stored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 struct SessionRecord {
-    data: Vec<u8>,
+    data: SessionData,
+    expiry_date: i64,
+}
+
+/// Format in which the data of a session is stored, see
+/// [`SurrealSessionStore::with_serialization_format`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SerializationFormat {
+    /// Compact binary [MessagePack](https://msgpack.org), stored as bytes.
+    #[default]
+    MessagePack,
+    /// Human readable JSON, stored as a string.
+    Json,
+    /// The data map of the session, stored as a native SurrealDB object
+    /// which can be queried, e.g. `select * from sessions where
+    /// data.user_id = 42`. The id and expiry date are taken from the
+    /// record key and the `expiry_date` field, so the expiry date is
+    /// only stored to the second.
+    ///
+    /// Session values must convert cleanly to SurrealDB values: integers
+    /// beyond the `i64` range are not preserved exactly.
+    Native,
+}
+
+/// How sessions are encoded in the [`SerializationFormat::MessagePack`]
+/// format, see [`SurrealSessionStore::with_message_pack_encoding`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessagePackEncoding {
+    /// Fields are encoded as a map keyed by field name, which other
+    /// languages decode without knowing the field order, and which
+    /// older versions of this crate decode after fields are added.
+    #[default]
+    Named,
+    /// Fields are encoded as an array in declaration order, which is
+    /// smaller but can only be decoded knowing the fields of
+    /// [`Record`]. Used by earlier versions of this crate.
+    Positional,
+}
+
+/// What loading a session that fails to decode does, see
+/// [`SurrealSessionStore::with_decode_failure_policy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecodeFailure {
+    /// The load fails with the decode error, which the session layer may
+    /// turn into an error response.
+    #[default]
+    Error,
+    /// The load returns no session, so the user starts a new one. With
+    /// `delete`, the undecodable session is also deleted, unless it was
+    /// rewritten in the meantime.
+    TreatAsMissing {
+        /// Whether to delete the undecodable session.
+        delete: bool,
+    },
+}
+
+/// Encoded session as stored in the `data` field, in one of the
+/// [`SerializationFormat`]s. The format is recognized from the type of
+/// the stored value, so sessions are decoded whatever the configured
+/// format.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+enum SessionData {
+    MessagePack(Vec<u8>),
+    Json(String),
+    Native(HashMap<String, serde_json::Value>),
+}
+
+impl SessionData {
+    /// The stored data as bytes, with native data encoded as JSON.
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            SessionData::MessagePack(bytes) => Cow::Borrowed(bytes),
+            SessionData::Json(json) => Cow::Borrowed(json.as_bytes()),
+            SessionData::Native(data) => {
+                Cow::Owned(serde_json::to_vec(data).expect("JSON values always serialize to JSON"))
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    /// The format the data is stored in.
+    fn format(&self) -> SerializationFormat {
+        match self {
+            SessionData::MessagePack(_) => SerializationFormat::MessagePack,
+            SessionData::Json(_) => SerializationFormat::Json,
+            SessionData::Native(_) => SerializationFormat::Native,
+        }
+    }
+
+    /// Data stored as bytes after decrypting or decompressing it. A JSON
+    /// encoded session starts with `{`, which is never the first byte of
+    /// a MessagePack encoded one.
+    #[cfg(any(feature = "compression", feature = "encryption"))]
+    fn from_bytes(bytes: Vec<u8>) -> StoreResult<Self> {
+        if bytes.first() == Some(&b'{') {
+            let json = String::from_utf8(bytes).map_err(|e| Error::Decode(e.to_string()))?;
+            Ok(SessionData::Json(json))
+        } else {
+            Ok(SessionData::MessagePack(bytes))
+        }
+    }
+}
+
+/// The stored fields of a session making up its [`SessionMeta`].
+#[derive(Deserialize, Debug)]
+struct SessionMetaRecord {
     expiry_date: i64,
+    user_id: Option<String>,
+    pinned: Option<bool>,
+}
+
+/// The parts of the result of `info for db` used by the store.
+#[derive(Deserialize, Debug)]
+struct DatabaseInfo {
+    tables: BTreeMap<String, IgnoredAny>,
+}
+
+/// A [`SessionRecord`] as written to the table, with the expiry date
+/// stored under the configured column name, the user id of the session
+/// if it has one, and whether it is pinned if that is to be changed.
+#[derive(Debug, Clone)]
+struct StoredSessionRecord {
+    record: SessionRecord,
+    expiry_column: String,
+    user_id: Option<String>,
+    pinned: Option<bool>,
+}
+
+impl Serialize for StoredSessionRecord {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(
+            2 + usize::from(self.user_id.is_some()) + usize::from(self.pinned.is_some()),
+        ))?;
+        map.serialize_entry("data", &self.record.data)?;
+        map.serialize_entry(&self.expiry_column, &self.record.expiry_date)?;
+        if let Some(user_id) = &self.user_id {
+            map.serialize_entry(USER_ID_COLUMN, user_id)?;
+        }
+        if let Some(pinned) = self.pinned {
+            map.serialize_entry(PINNED_COLUMN, &pinned)?;
+        }
+        map.end()
+    }
+}
+
+/// A [`StoredSessionRecord`] along with its record key, as written by
+/// [`SurrealSessionStore::save_many`].
+#[derive(Serialize, Debug, Clone)]
+struct KeyedStoredSessionRecord {
+    key: String,
+    record: StoredSessionRecord,
+}
+
+/// The old and new record key of a session moved by
+/// [`SurrealSessionStore::rekey_prefix`].
+#[derive(Serialize, Debug)]
+struct KeyMove {
+    old: String,
+    new: String,
+}
+
+/// A [`SessionRecord`] along with its record key.
+#[derive(Serialize, Deserialize, Debug)]
+struct KeyedSessionRecord {
+    key: String,
+    #[serde(flatten)]
+    record: SessionRecord,
 }
 
 impl SessionRecord {
-    fn from_session(session: &Record) -> Result<Self> {
+    /// Encode `session` in `format`, or with `codec` as bytes if given.
+    fn from_session(
+        session: &Record,
+        format: SerializationFormat,
+        encoding: MessagePackEncoding,
+        codec: Option<&dyn SessionCodec>,
+    ) -> StoreResult<Self> {
+        let data = match (codec, format) {
+            (Some(codec), _) => SessionData::MessagePack(codec.encode(session)?),
+            (None, SerializationFormat::MessagePack) => {
+                SessionData::MessagePack(MessagePackCodec::new(encoding).encode(session)?)
+            }
+            (None, SerializationFormat::Json) => SessionData::Json(
+                serde_json::to_string(session).map_err(|e| Error::Encode(e.to_string()))?,
+            ),
+            (None, SerializationFormat::Native) => SessionData::Native(session.data.clone()),
+        };
         Ok(SessionRecord {
-            data: rmp_serde::to_vec(session).map_err(|e| Error::Decode(e.to_string()))?,
+            data,
             expiry_date: session.expiry_date.unix_timestamp(),
         })
     }
 
-    fn to_session(&self) -> Result<Record> {
-        let session: Record =
-            rmp_serde::from_slice(&self.data).map_err(|e| Error::Decode(e.to_string()))?;
+    /// Check that a record returned by the database has the fields of a
+    /// session record, which may not be the case if e.g. a schemafull
+    /// table does not define them.
+    fn check_fields(
+        record: &BTreeMap<String, IgnoredAny>,
+        data_column: &str,
+        expiry_column: &str,
+    ) -> StoreResult<()> {
+        let fields = [data_column, expiry_column];
+        let missing: Vec<&str> = fields
+            .into_iter()
+            .filter(|field| !record.contains_key(*field))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let received: Vec<&str> = record.keys().map(String::as_str).collect();
+        Err(SurrealStoreError::schema_mismatch(format!(
+            "Saved session record has an unexpected shape: expected fields {fields:?}, missing {missing:?}, received {received:?}"
+        )))
+    }
+
+    /// Whether the session was encoded positionally, as by older
+    /// versions of this crate.
+    fn is_legacy_encoding(&self) -> bool {
+        self.message_pack_encoding() == Some(MessagePackEncoding::Positional)
+    }
+
+    /// The encoding of MessagePack data, or `None` for other formats and
+    /// sealed data. The first byte of the data is the MessagePack marker
+    /// of the encoded [`Record`], which is a map for the named encoding
+    /// and an array for the positional one.
+    fn message_pack_encoding(&self) -> Option<MessagePackEncoding> {
+        let SessionData::MessagePack(bytes) = &self.data else {
+            return None;
+        };
+        match bytes.first() {
+            Some(0x80..=0x8f | 0xde | 0xdf) => Some(MessagePackEncoding::Named),
+            Some(0x90..=0x9f | 0xdc | 0xdd) => Some(MessagePackEncoding::Positional),
+            _ => None,
+        }
+    }
+
+    /// Decode the session stored under `session_id`, which is only used
+    /// by natively stored sessions since the other formats include it.
+    /// Data stored as bytes is decoded with `codec` if given.
+    ///
+    /// The stored expiry date takes precedence over the one encoded in
+    /// the data, which is stale after a [touch](SurrealSessionStore::touch).
+    /// The encoded one is kept if it matches, since it is more precise.
+    fn to_session(&self, session_id: &Id, codec: Option<&dyn SessionCodec>) -> StoreResult<Record> {
+        let mut session: Record = match &self.data {
+            SessionData::MessagePack(bytes) => match codec {
+                Some(codec) => codec.decode(bytes)?,
+                None => MessagePackCodec::default().decode(bytes)?,
+            },
+            SessionData::Json(json) => {
+                serde_json::from_str(json).map_err(|e| Error::Decode(e.to_string()))?
+            }
+            SessionData::Native(data) => Record {
+                id: *session_id,
+                data: data.clone(),
+                expiry_date: OffsetDateTime::from_unix_timestamp(self.expiry_date)
+                    .map_err(|e| Error::Decode(e.to_string()))?,
+            },
+        };
+        if session.expiry_date.unix_timestamp() != self.expiry_date {
+            session.expiry_date = OffsetDateTime::from_unix_timestamp(self.expiry_date)
+                .map_err(|e| Error::Decode(e.to_string()))?;
+        }
         Ok(session)
     }
 }
 
 /// A SurrealDB session store.
+///
+/// # Consistency
+///
+/// Each store operation runs as a single statement, so it is atomic and
+/// sees a consistent view of the session it reads or writes. Separate
+/// operations are not isolated from each other: a session loaded by one
+/// call may have been changed or deleted by the time the next call
+/// runs. To read a session together with related records consistently,
+/// use [`load_consistent`](Self::load_consistent).
 #[derive(Debug, Clone)]
 pub struct SurrealSessionStore<DB: std::fmt::Debug + surrealdb::Connection> {
     client: Surreal<DB>,
     session_table: String,
+    keep_alive: Option<Arc<KeepAlive>>,
+    blocking_threshold: Option<usize>,
+    key_prefix: Option<String>,
+    key_separator: String,
+    error_on_missing_delete: bool,
+    audit_table: Option<String>,
+    require_existing_table: bool,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    migrate_on_read: bool,
+    max_decode_size: Option<usize>,
+    decode_failure: DecodeFailure,
+    skip_unchanged_saves: bool,
+    max_data_bytes: Option<usize>,
+    user_id_key: Option<String>,
+    expiry_index: Option<String>,
+    format: SerializationFormat,
+    message_pack_encoding: MessagePackEncoding,
+    codec: Option<Codec>,
+    data_column: String,
+    expiry_column: String,
+    datetime_expiry: bool,
+    #[cfg(feature = "encryption")]
+    cipher: Option<encryption::Cipher>,
+    #[cfg(feature = "compression")]
+    compression: Option<u32>,
+    retry: Option<RetryPolicy>,
+    credentials: Option<Credentials>,
+    id_generator: Option<IdGenerator>,
+    validator: Option<Validator>,
+    page_size: usize,
+    timeout: Option<Duration>,
+    clock_skew_warning: Option<Duration>,
+    metrics: Option<Metrics>,
+    scope: Option<(String, String)>,
+}
+
+/// Configuration of the circuit breaker, see
+/// [`SurrealSessionStore::with_circuit_breaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Number of consecutive backend failures that opens the circuit.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before probing the backend.
+    pub cooldown: Duration,
+}
+
+/// Outcome of [`SurrealSessionStore::migrate_records`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Number of sessions rewritten in the current encoding.
+    pub migrated: u64,
+    /// Number of sessions already in the current encoding.
+    pub skipped: u64,
+    /// Record keys of the sessions that could not be decoded or
+    /// rewritten.
+    pub failed: Vec<String>,
+}
+
+/// Operations the current user may run on the session table, see
+/// [`SurrealSessionStore::check_table_access`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableAccessReport {
+    /// Whether sessions can be read, needed to load sessions.
+    pub select: bool,
+    /// Whether sessions can be created, needed to create and save
+    /// sessions.
+    pub create: bool,
+    /// Whether sessions can be updated, needed to save sessions.
+    pub update: bool,
+    /// Whether sessions can be deleted, needed to delete sessions.
+    pub delete: bool,
+}
+
+/// What is stored about a session besides its data, see
+/// [`SurrealSessionStore::load_metadata`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionMeta {
+    /// When the session expires, to the second.
+    pub expiry_date: OffsetDateTime,
+    /// The user id stored with the session, see
+    /// [`SurrealSessionStore::with_user_id_key`].
+    pub user_id: Option<String>,
+    /// Whether the session is pinned, see
+    /// [`SurrealSessionStore::save_pinned`].
+    pub pinned: bool,
+}
+
+/// Retries of store operations failing with a backend error, see
+/// [`SurrealSessionStore::with_retry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of attempts in total, including the first.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubling for each further retry.
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay after the given failed attempt, counting from 1.
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+}
+
+/// Credentials the store signs in with again when the client's
+/// authentication expires, see [`SurrealSessionStore::with_credentials`].
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// A root user.
+    Root { username: String, password: String },
+    /// A user of `namespace`.
+    Namespace {
+        namespace: String,
+        username: String,
+        password: String,
+    },
+    /// A user of `database` in `namespace`.
+    Database {
+        namespace: String,
+        database: String,
+        username: String,
+        password: String,
+    },
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Credentials::Root { username, .. } => f
+                .debug_struct("Root")
+                .field("username", username)
+                .finish_non_exhaustive(),
+            Credentials::Namespace {
+                namespace,
+                username,
+                ..
+            } => f
+                .debug_struct("Namespace")
+                .field("namespace", namespace)
+                .field("username", username)
+                .finish_non_exhaustive(),
+            Credentials::Database {
+                namespace,
+                database,
+                username,
+                ..
+            } => f
+                .debug_struct("Database")
+                .field("namespace", namespace)
+                .field("database", database)
+                .field("username", username)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Parts of the errors SurrealDB returns when the client's
+/// authentication is invalid or has expired.
+const AUTH_FAILURES: [&str; 2] = [
+    "The session has expired",
+    "There was a problem with authentication",
+];
+
+/// How often [`SurrealSessionStore::spawn_cleanup_per_table`] cleans
+/// each table: a default interval shared by all tables, which can be
+/// overridden per table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanupIntervals {
+    default: Duration,
+    per_table: HashMap<String, Duration>,
+}
+
+impl CleanupIntervals {
+    /// Clean every table each `default` interval.
+    pub fn new(default: Duration) -> Self {
+        Self {
+            default,
+            per_table: HashMap::new(),
+        }
+    }
+
+    /// Clean `table` each `period` instead of the default interval.
+    pub fn with_table(mut self, table: impl Into<String>, period: Duration) -> Self {
+        self.per_table.insert(table.into(), period);
+        self
+    }
+
+    /// The interval at which `table` is cleaned.
+    pub fn for_table(&self, table: &str) -> Duration {
+        self.per_table.get(table).copied().unwrap_or(self.default)
+    }
+}
+
+/// Position of a paged export of the session table.
+enum ExportCursor {
+    /// The page has already been fetched.
+    Fetched(Vec<KeyedSessionRecord>),
+    /// The page after the record with the given key, or the first page.
+    After(Option<String>),
+    /// The last page has been exported.
+    Done,
+}
+
+/// Generator of new session ids, see
+/// [`SurrealSessionStore::with_id_generator`].
+#[derive(Clone)]
+struct IdGenerator(Arc<dyn Fn() -> Id + Send + Sync>);
+
+impl std::fmt::Debug for IdGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("IdGenerator(..)")
+    }
+}
+
+/// Check of sessions before they are written, see
+/// [`SurrealSessionStore::with_validator`].
+#[derive(Clone)]
+struct Validator(Arc<dyn Fn(&Record) -> std::result::Result<(), String> + Send + Sync>);
+
+impl std::fmt::Debug for Validator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Validator(..)")
+    }
+}
+
+/// Encoding of sessions, see [`SurrealSessionStore::with_codec`].
+#[derive(Clone)]
+struct Codec(Arc<dyn SessionCodec>);
+
+impl std::fmt::Debug for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Codec(..)")
+    }
+}
+
+/// Receiver of the store's metrics, see
+/// [`SurrealSessionStore::with_metrics`].
+#[derive(Clone)]
+struct Metrics(Arc<dyn SessionMetrics>);
+
+impl std::fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Metrics(..)")
+    }
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: Mutex<CircuitState>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CircuitState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen,
+}
+
+impl CircuitBreaker {
+    /// Check whether an operation may contact the backend.
+    fn acquire(&self) -> StoreResult<()> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match *state {
+            CircuitState::Closed { .. } => Ok(()),
+            CircuitState::Open { until } if Instant::now() >= until => {
+                *state = CircuitState::HalfOpen;
+                Ok(())
+            }
+            CircuitState::Open { .. } | CircuitState::HalfOpen => Err(
+                SurrealStoreError::connection("Circuit breaker open, not contacting the database"),
+            ),
+        }
+    }
+
+    /// Update the state with the result of an operation.
+    fn record<T>(&self, result: &StoreResult<T>) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let failed = matches!(result, Err(e) if matches!(e.inner(), Error::Backend(_)));
+        *state = match *state {
+            _ if !failed => CircuitState::Closed { failures: 0 },
+            CircuitState::Closed { failures } if failures + 1 < self.config.failure_threshold => {
+                CircuitState::Closed {
+                    failures: failures + 1,
+                }
+            }
+            _ => {
+                warn!("Session store circuit breaker opened");
+                CircuitState::Open {
+                    until: Instant::now() + self.config.cooldown,
+                }
+            }
+        };
+    }
+}
+
+/// Background task pinging the database, stopped when the last store
+/// holding it is dropped.
+#[derive(Debug)]
+struct KeepAlive(tokio::task::JoinHandle<()>);
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
 }
 
 impl<DB: std::fmt::Debug + surrealdb::Connection> SurrealSessionStore<DB> {
     /// Create a new SurrealDB session store with the provided client,
     /// storing sessions in the given table. Note that the table must
-    /// be defined ahead of time if strict mode is enabled.
+    /// be defined ahead of time if strict mode is enabled, e.g. with
+    /// [`define_table`](Self::define_table).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `session_table` is not a valid table name, see
+    /// [`try_new`](Self::try_new).
     pub fn new(client: Surreal<DB>, session_table: String) -> Self {
-        Self {
+        match Self::try_new(client, session_table) {
+            Ok(store) => store,
+            Err(e) => panic!("Invalid session table name: {e}"),
+        }
+    }
+
+    /// Create a new SurrealDB session store like [`new`](Self::new),
+    /// storing sessions in the [`DEFAULT_SESSION_TABLE`].
+    pub fn new_default(client: Surreal<DB>) -> Self {
+        Self::new(client, DEFAULT_SESSION_TABLE.to_string())
+    }
+
+    /// Create a new SurrealDB session store like [`new`](Self::new), or
+    /// fail if `session_table` is not a valid table name. Since the name
+    /// is put into some queries as is, it may only consist of ASCII
+    /// letters, digits and underscores.
+    pub fn try_new(client: Surreal<DB>, session_table: String) -> StoreResult<Self> {
+        validate_identifier(&session_table)?;
+        let expiry_index = format!("{session_table}_expiry_date");
+        Ok(Self {
             client,
             session_table,
-        }
+            keep_alive: None,
+            blocking_threshold: None,
+            key_prefix: None,
+            key_separator: DEFAULT_KEY_SEPARATOR.to_string(),
+            error_on_missing_delete: false,
+            audit_table: None,
+            require_existing_table: false,
+            circuit_breaker: None,
+            migrate_on_read: false,
+            max_decode_size: None,
+            decode_failure: DecodeFailure::default(),
+            skip_unchanged_saves: false,
+            max_data_bytes: None,
+            user_id_key: None,
+            expiry_index: Some(expiry_index),
+            format: SerializationFormat::default(),
+            message_pack_encoding: MessagePackEncoding::default(),
+            codec: None,
+            data_column: DEFAULT_DATA_COLUMN.to_string(),
+            expiry_column: DEFAULT_EXPIRY_COLUMN.to_string(),
+            datetime_expiry: false,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+            #[cfg(feature = "compression")]
+            compression: None,
+            retry: None,
+            credentials: None,
+            id_generator: None,
+            validator: None,
+            page_size: EXPORT_PAGE_SIZE,
+            timeout: None,
+            clock_skew_warning: None,
+            metrics: None,
+            scope: None,
+        })
     }
-}
 
-#[async_trait]
-impl<DB: std::fmt::Debug + surrealdb::Connection> ExpiredDeletion for SurrealSessionStore<DB> {
-    async fn delete_expired(&self) -> Result<()> {
-        info!("Deleting expired sessions");
-        self.client
-            .query(
-                "delete type::table($table) where expiry_date <= time::unix(time::now())"
-                    .to_string(),
-            )
+    /// Create a new SurrealDB session store like [`try_new`](Self::try_new)
+    /// and check that it can read the session table, failing at startup
+    /// rather than on the first request if the client can't reach the
+    /// database, has no namespace or database selected, or lacks the
+    /// table in strict mode. The check reads at most one session.
+    pub async fn connect(client: Surreal<DB>, session_table: String) -> StoreResult<Self> {
+        let store = Self::try_new(client, session_table)?;
+        store.check_readable().await?;
+        Ok(store)
+    }
+
+    /// Check that the session table can be read, reading at most one
+    /// session.
+    async fn check_readable(&self) -> StoreResult<()> {
+        self.query("select value id from type::table($table) limit 1")
             .bind(("table", self.session_table.clone()))
             .await
             .map_err(|e| Error::Backend(e.to_string()))?
@@ -68,146 +959,6644 @@ impl<DB: std::fmt::Debug + surrealdb::Connection> ExpiredDeletion for SurrealSes
             .map_err(|e| Error::Backend(e.to_string()))?;
         Ok(())
     }
-}
 
-#[async_trait]
-impl<DB: std::fmt::Debug + surrealdb::Connection> SessionStore for SurrealSessionStore<DB> {
-    async fn create(&self, session: &mut Record) -> Result<()> {
-        while self
-            .client
-            .select::<Option<SessionRecord>>((self.session_table.clone(), session.id.to_string()))
-            .await
-            .map_err(|e| Error::Backend(e.to_string()))?
-            .is_some()
-        {
-            session.id = Id::default();
+    /// A store like this one, sharing its client and configuration, but
+    /// storing sessions in `session_table` instead, e.g. to keep admin
+    /// sessions apart from those of end users. Cloning a store is cheap,
+    /// so this can be called per request. Fails if `session_table` is
+    /// not a valid table name, see [`try_new`](Self::try_new).
+    pub fn for_table(&self, session_table: &str) -> StoreResult<Self> {
+        validate_identifier(session_table)?;
+        Ok(Self {
+            session_table: session_table.to_string(),
+            ..self.clone()
+        })
+    }
+
+    /// The client the store queries, e.g. to run custom queries on the
+    /// same connection. Queries run on it directly use the namespace and
+    /// database selected on the client, not a [scope](Self::with_scope)
+    /// set on the store.
+    pub fn client(&self) -> &Surreal<DB> {
+        &self.client
+    }
+
+    /// The name of the table sessions are stored in.
+    pub fn session_table(&self) -> &str {
+        &self.session_table
+    }
+
+    /// Name of the field holding the expiry date of a session,
+    /// `expiry_date` by default. Since the name is put into queries
+    /// as is, it may only consist of ASCII letters, digits and
+    /// underscores; other names are rejected with an error.
+    pub fn with_expiry_column(mut self, expiry_column: String) -> StoreResult<Self> {
+        validate_identifier(&expiry_column)?;
+        self.expiry_column = expiry_column;
+        Ok(self)
+    }
+
+    /// Name of the field holding the encoded data of a session, `data`
+    /// by default, e.g. to store sessions in an existing table whose
+    /// `data` field means something else. Like
+    /// [`with_expiry_column`](Self::with_expiry_column), names other
+    /// than ASCII letters, digits and underscores are rejected with an
+    /// error. Sessions stored under the previous name are not found.
+    pub fn with_data_column(mut self, data_column: String) -> StoreResult<Self> {
+        validate_identifier(&data_column)?;
+        self.data_column = data_column;
+        Ok(self)
+    }
+
+    /// Fetch `page_size` sessions per query when scanning the whole
+    /// table, as in [`export_stream`](Self::export_stream),
+    /// [`stream_all`](Self::stream_all) and
+    /// [`migrate_records`](Self::migrate_records). Larger pages take
+    /// fewer round trips but more memory. Defaults to
+    /// [`EXPORT_PAGE_SIZE`]; fails if `page_size` is 0.
+    pub fn with_page_size(mut self, page_size: usize) -> StoreResult<Self> {
+        if page_size == 0 {
+            return Err(SurrealStoreError::invalid_configuration(
+                "Page size must not be 0",
+            ));
         }
-        self.save(session).await
+        self.page_size = page_size;
+        Ok(self)
     }
 
-    async fn save(&self, session: &Record) -> Result<()> {
-        let _: SessionRecord = self
-            .client
-            .upsert((self.session_table.clone(), session.id.to_string()))
-            .content(SessionRecord::from_session(session)?)
-            .await
-            .map_err(|e| Error::Backend(e.to_string()))?
-            .ok_or(Error::Backend("Session record not saved".to_string()))?;
+    /// Whether expiry dates are stored as SurrealDB datetimes rather than
+    /// unix timestamps in seconds, the default. Datetimes are readable
+    /// when browsing the table and can be used with `time::` functions
+    /// directly. Either way, expiry dates are stored to the second.
+    ///
+    /// Existing sessions are not converted, and sessions with an expiry
+    /// date of the other kind are never found to be live or expired, so
+    /// convert them when switching, e.g. with `update sessions set
+    /// expiry_date = time::from::unix(expiry_date) where
+    /// type::is::int(expiry_date)`. A field defined by
+    /// [`define_table`](Self::define_table) must be redefined with the
+    /// new type.
+    pub fn with_datetime_expiry(mut self, datetime_expiry: bool) -> Self {
+        self.datetime_expiry = datetime_expiry;
+        self
+    }
 
-        Ok(())
+    /// SurrealQL expression of the current time, comparable with stored
+    /// expiry dates.
+    fn now(&self) -> &'static str {
+        if self.datetime_expiry {
+            "time::now()"
+        } else {
+            "time::unix(time::now())"
+        }
     }
 
-    async fn load(&self, session_id: &Id) -> Result<Option<Record>> {
-        let record: Option<SessionRecord> = self
-            .client
-            .query(
-                "select expiry_date, data from type::thing($table, $id)
-where expiry_date > time::unix(time::now())",
-            )
-            .bind(("id", session_id.to_string()))
-            .bind(("table", self.session_table.clone()))
+    /// SurrealQL projection of the stored data of a session as the
+    /// `data` field of a [`SessionRecord`].
+    fn data_field(&self) -> String {
+        format!("{} as data", self.data_column)
+    }
+
+    /// SurrealQL expression of the stored expiry date as a unix
+    /// timestamp in seconds.
+    fn unix_expiry(&self) -> String {
+        if self.datetime_expiry {
+            format!("time::unix({})", self.expiry_column)
+        } else {
+            self.expiry_column.clone()
+        }
+    }
+
+    /// SurrealQL expression converting the unix timestamp `unix` to a
+    /// stored expiry date.
+    fn expiry_at(&self, unix: &str) -> String {
+        if self.datetime_expiry {
+            format!("time::from::unix({unix})")
+        } else {
+            unix.to_string()
+        }
+    }
+
+    /// SurrealQL content of a session written from the
+    /// [`StoredSessionRecord`] `record`. A session that is overwritten
+    /// stays pinned or not unless `record` says otherwise.
+    fn record_content(&self, record: &str) -> String {
+        let column = &self.expiry_column;
+        let user_id = match self.user_id_key {
+            Some(_) => format!(", {USER_ID_COLUMN}: {record}.{USER_ID_COLUMN}"),
+            None => String::new(),
+        };
+        format!(
+            "{{ {data}: {record}.data, {column}: {}{user_id}, {PINNED_COLUMN}: {record}.{PINNED_COLUMN} ?? {PINNED_COLUMN} }}",
+            self.expiry_at(&format!("{record}.{column}")),
+            data = self.data_column
+        )
+    }
+
+    /// Run every query of the store in the namespace `namespace` and
+    /// database `database`, rather than the ones selected on the client.
+    /// The scope is set per query, so stores sharing a client, or clones
+    /// of it, can use different databases concurrently without
+    /// interfering. Like table names, the names may only consist of
+    /// ASCII letters, digits and underscores.
+    ///
+    /// With a scope, the results of the statements passed to
+    /// [`load_consistent`](Self::load_consistent) start at index 2.
+    pub fn with_scope(mut self, namespace: String, database: String) -> StoreResult<Self> {
+        validate_identifier(&namespace)?;
+        validate_identifier(&database)?;
+        self.scope = Some((namespace, database));
+        Ok(self)
+    }
+
+    /// Build a query running `statements` in the scope of the store, see
+    /// [`with_scope`](Self::with_scope).
+    fn query(&self, statements: impl Into<String>) -> surrealdb::method::Query<'_, DB> {
+        match &self.scope {
+            Some((namespace, database)) => self.client.query(format!(
+                "use ns {namespace} db {database};\n{}",
+                statements.into()
+            )),
+            None => self.client.query(statements.into()),
+        }
+    }
+
+    /// Index of the first statement of a query built by
+    /// [`query`](Self::query) in its response, after the `use` statement
+    /// setting the scope if any.
+    fn first_statement(&self) -> usize {
+        usize::from(self.scope.is_some())
+    }
+
+    /// Create a store writing the data of sessions as native SurrealDB
+    /// objects, see [`SerializationFormat::Native`].
+    pub fn new_with_native_data(client: Surreal<DB>, session_table: String) -> Self {
+        Self::new(client, session_table).with_serialization_format(SerializationFormat::Native)
+    }
+
+    /// Create a store encrypting the data of sessions with AES-256-GCM
+    /// under `key`, available with the `encryption` feature. The expiry
+    /// date is stored in the clear, so expired sessions can still be
+    /// found and deleted.
+    ///
+    /// Sessions stored unencrypted, or encrypted under another key, fail
+    /// to load. The data of a session is also bound to its record, table
+    /// and key, so data copied to another record fails to load too; this
+    /// includes sessions moved by [`rekey_prefix`](Self::rekey_prefix).
+    /// [`SerializationFormat::Native`] data can't be queried once
+    /// encrypted, so it is encrypted as JSON.
+    #[cfg(feature = "encryption")]
+    pub fn new_encrypted(client: Surreal<DB>, session_table: String, key: [u8; 32]) -> Self {
+        Self {
+            cipher: Some(encryption::Cipher::new(key)),
+            ..Self::new(client, session_table)
+        }
+    }
+
+    /// Format in which session data is written, MessagePack by default.
+    /// JSON is larger and slower, but readable by tools querying the
+    /// table directly. Sessions are read whichever format they were
+    /// written in, so the format can be changed on an existing table.
+    /// Ignored while a [codec](Self::with_codec) is configured.
+    pub fn with_serialization_format(mut self, format: SerializationFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// How sessions are encoded in the MessagePack format, by field name
+    /// by default. Sessions are read whichever encoding they were
+    /// written in, and [`migrate_records`](Self::migrate_records)
+    /// rewrites sessions in the other encoding.
+    pub fn with_message_pack_encoding(mut self, encoding: MessagePackEncoding) -> Self {
+        self.message_pack_encoding = encoding;
+        self
+    }
+
+    /// Encode sessions with `codec` and store them as bytes, instead of
+    /// in the [serialization format](Self::with_serialization_format),
+    /// e.g. to store sessions in a format of the application's choice.
+    /// Encoded sessions are still compressed and encrypted if enabled.
+    /// `None`, the default, uses the serialization format.
+    ///
+    /// The codec takes the place of the serialization format, so a
+    /// configured [`SerializationFormat::Json`] or
+    /// [`SerializationFormat::Native`] is ignored while it is set:
+    /// sessions are always written as bytes, which can't be read or
+    /// queried as JSON or objects in the database.
+    ///
+    /// Sessions stored as bytes are decoded with the codec, so configure
+    /// it on a new table or one whose sessions it can decode. Sessions
+    /// stored as JSON strings or native objects still load, and
+    /// [`migrate_records`](Self::migrate_records) rewrites them with the
+    /// codec.
+    pub fn with_codec(mut self, codec: Option<Arc<dyn SessionCodec>>) -> Self {
+        self.codec = codec.map(Codec);
+        self
+    }
+
+    /// The configured codec, if any.
+    fn codec(&self) -> Option<&dyn SessionCodec> {
+        self.codec.as_ref().map(|codec| codec.0.as_ref())
+    }
+
+    /// Name of the index on the expiry date defined by
+    /// [`ensure_table`](Self::ensure_table), `<table>_expiry_date` by
+    /// default. `None` skips defining the index.
+    pub fn with_expiry_index(mut self, expiry_index: Option<String>) -> Self {
+        self.expiry_index = expiry_index;
+        self
+    }
+
+    /// Define the session table and the index on the expiry date if they
+    /// don't exist yet. Call this once at startup.
+    ///
+    /// Without the index, expiring sessions in
+    /// [`delete_expired`](ExpiredDeletion::delete_expired) and filtering
+    /// out expired sessions when loading or counting scan the whole
+    /// table, which gets slow as the table grows.
+    pub async fn ensure_table(&self) -> StoreResult<()> {
+        let table = validate_identifier(&self.session_table)?;
+        let mut statements = format!("define table if not exists {table};");
+        if let Some(index) = &self.expiry_index {
+            let index = validate_identifier(index)?;
+            statements.push_str(&format!(
+                "define index if not exists {index} on table {table} fields {};",
+                self.expiry_column
+            ));
+        }
+        if self.user_id_key.is_some() {
+            statements.push_str(&format!(
+                "define index if not exists {table}_{USER_ID_COLUMN} on table {table} fields {USER_ID_COLUMN};"
+            ));
+        }
+        self.query(statements)
             .await
             .map_err(|e| Error::Backend(e.to_string()))?
-            .take(0)
+            .check()
             .map_err(|e| Error::Backend(e.to_string()))?;
-        record.map(|r| r.to_session()).transpose()
+        Ok(())
+    }
+
+    /// Define the session table like [`ensure_table`](Self::ensure_table),
+    /// along with its data and expiry date fields, if they don't exist
+    /// yet. This bootstraps a database in strict mode, where tables must
+    /// be defined before use. The data field accepts every
+    /// [`SerializationFormat`].
+    pub async fn define_table(&self) -> StoreResult<()> {
+        self.ensure_table().await?;
+        let table = validate_identifier(&self.session_table)?;
+        // MessagePack data is written as an array of its bytes rather
+        // than as a SurrealDB bytes value.
+        let mut statements = format!(
+            "define field if not exists {data} on table {table} type bytes | array<int> | string | object;
+define field if not exists {column} on table {table} type {kind};
+define field if not exists {PINNED_COLUMN} on table {table} type option<bool>;",
+            data = self.data_column,
+            column = self.expiry_column,
+            kind = if self.datetime_expiry {
+                "datetime"
+            } else {
+                "int"
+            }
+        );
+        if self.user_id_key.is_some() {
+            statements.push_str(&format!(
+                "define field if not exists {USER_ID_COLUMN} on table {table} type option<string>;"
+            ));
+        }
+        self.query(statements)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .check()
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Define an event on the session table deleting expired sessions
+    /// whenever a new session is created, so expired sessions are purged
+    /// by the database itself as sessions come and go. Requires
+    /// SurrealDB 2.0 or later. Call this once at startup, after the
+    /// table is defined if strict mode is enabled.
+    ///
+    /// SurrealDB has no scheduled deletion, so nothing is purged while
+    /// no sessions are created; run
+    /// [`delete_expired`](ExpiredDeletion::delete_expired) now and then
+    /// to bound how long expired sessions linger. Each creation also
+    /// pays for the delete, which is cheap with the index defined by
+    /// [`ensure_table`](Self::ensure_table).
+    pub async fn define_expiry_event(&self) -> StoreResult<()> {
+        let table = validate_identifier(&self.session_table)?;
+        self.query(format!(
+            "define event if not exists {table}_purge_expired on table {table}
+when $event = 'CREATE'
+then (delete {table} where {column} <= {now} and {PINNED_COLUMN} != true);",
+            column = self.expiry_column,
+            now = self.now()
+        ))
+        .await
+        .map_err(|e| Error::Backend(e.to_string()))?
+        .check()
+        .map_err(|e| Error::Backend(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Refuse to decode stored session data larger than `max_size`
+    /// bytes, returning a decode error instead. This protects the server
+    /// from exhausting memory on oversized sessions, since the size is
+    /// checked before decoding. Compressed data is also checked as it
+    /// is decompressed. `None`, the default, sets no limit.
+    pub fn with_max_decode_size(mut self, max_size: Option<usize>) -> Self {
+        self.max_decode_size = max_size;
+        self
+    }
+
+    /// Handle sessions that fail to decode when loaded, e.g. because
+    /// they were corrupted or written by an incompatible version, with
+    /// `policy`. [`DecodeFailure::TreatAsMissing`] recovers users from
+    /// such sessions by starting them a new one, logging a warning. Only
+    /// [`load`](SessionStore::load) is affected. The default is
+    /// [`DecodeFailure::Error`].
+    pub fn with_decode_failure_policy(mut self, policy: DecodeFailure) -> Self {
+        self.decode_failure = policy;
+        self
+    }
+
+    /// Skip the write of a [`save`](SessionStore::save) if the stored
+    /// session already has the same encoded data and expiry date, e.g.
+    /// when a session is saved again without changes. The stored session
+    /// is read before each save to compare it, trading an extra read for
+    /// the write and the events, audit records and replication it would
+    /// cause. Disabled by default.
+    ///
+    /// Encrypted sessions are encoded differently each time, so their
+    /// saves are never skipped.
+    pub fn with_skip_unchanged_saves(mut self, skip: bool) -> Self {
+        self.skip_unchanged_saves = skip;
+        self
+    }
+
+    /// Refuse to write sessions whose stored data is larger than
+    /// `max_size` bytes, failing the save or create with an encode error
+    /// instead. The size is checked after encoding, and after
+    /// compression and encryption if enabled, so it is the size of the
+    /// stored data. `None`, the default, sets no limit.
+    pub fn with_max_data_bytes(mut self, max_size: Option<usize>) -> Self {
+        self.max_data_bytes = max_size;
+        self
+    }
+
+    /// Store the value under `key` in the data of each session as the
+    /// session's user id, in the indexed `user_id` field, so that the
+    /// sessions of a user can be found with
+    /// [`list_session_ids_for_user`](Self::list_session_ids_for_user)
+    /// and [`delete_by_user`](Self::delete_by_user). String values are
+    /// stored as is and other values as JSON; sessions without the key,
+    /// or with a null value, have no user id. `None`, the default,
+    /// stores no user ids.
+    ///
+    /// The index is defined by [`ensure_table`](Self::ensure_table).
+    /// Sessions written before the key was set get a user id the next
+    /// time they are saved. The user id is stored in the clear, even if
+    /// session data is encrypted.
+    pub fn with_user_id_key(mut self, key: Option<String>) -> Self {
+        self.user_id_key = key;
+        self
+    }
+
+    /// The user id of a session, see [`with_user_id_key`](Self::with_user_id_key).
+    fn user_id(&self, session: &Record) -> Option<String> {
+        match session.data.get(self.user_id_key.as_ref()?)? {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(user_id) => Some(user_id.clone()),
+            value => Some(value.to_string()),
+        }
+    }
+
+    fn check_data_size(&self, record: &SessionRecord) -> StoreResult<()> {
+        match self.max_data_bytes {
+            Some(max_size) if record.data.len() > max_size => {
+                Err(SurrealStoreError::Serialization(Error::Encode(format!(
+                    "Session data of {} bytes exceeds the limit of {max_size} bytes",
+                    record.data.len()
+                ))))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_decode_size(&self, record: &SessionRecord) -> StoreResult<()> {
+        match self.max_decode_size {
+            Some(max_size) if record.data.len() > max_size => {
+                Err(SurrealStoreError::Serialization(Error::Decode(format!(
+                    "Session data of {} bytes exceeds the limit of {max_size} bytes",
+                    record.data.len()
+                ))))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether loading a session stored in an older encoding also
+    /// rewrites it in the current encoding, so that a table migrates
+    /// gradually through normal traffic instead of needing a separate
+    /// migration pass. Sessions in older encodings are readable either
+    /// way. The rewrite only applies if the session hasn't changed since
+    /// it was read, and a failed rewrite is logged rather than failing
+    /// the load. Defaults to `false`.
+    pub fn with_migrate_on_read(mut self, migrate_on_read: bool) -> Self {
+        self.migrate_on_read = migrate_on_read;
+        self
+    }
+
+    /// Stop contacting the database for a while after repeated backend
+    /// failures, failing fast instead of making every request wait for
+    /// a dead backend. After `failure_threshold` consecutive backend
+    /// errors, store operations fail immediately for `cooldown`; the
+    /// first operation after that is let through to probe the backend,
+    /// closing the circuit if it succeeds and reopening it if not. The
+    /// state is shared between clones of the store. Decode errors don't
+    /// count as failures. `None`, the default, disables the breaker.
+    /// Covers the operations listed under
+    /// [`with_timeout`](Self::with_timeout).
+    pub fn with_circuit_breaker(mut self, config: Option<CircuitBreakerConfig>) -> Self {
+        self.circuit_breaker = config.map(|config| {
+            Arc::new(CircuitBreaker {
+                config,
+                state: Mutex::new(CircuitState::Closed { failures: 0 }),
+            })
+        });
+        self
+    }
+
+    /// Retry store operations that fail with a backend error, such as a
+    /// dropped connection to a remote engine, waiting with exponential
+    /// backoff between attempts. Decode and encode errors are never
+    /// retried. With the circuit breaker enabled, each attempt counts
+    /// towards it. `None`, the default, disables retries.
+    ///
+    /// Writes are retried too, which is safe for saves and deletes. A
+    /// create whose response was lost may leave an unused session behind
+    /// until it expires. Covers the operations listed under
+    /// [`with_timeout`](Self::with_timeout).
+    pub fn with_retry(mut self, policy: Option<RetryPolicy>) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Sign in with `credentials` again and retry the operation once
+    /// when a store operation fails because the client's authentication
+    /// is invalid or has expired, e.g. when the token of a long-lived
+    /// connection to a remote engine expires. The client must already
+    /// be signed in; the credentials are only used to sign in again.
+    /// The retry doesn't count towards the [retries](Self::with_retry).
+    /// `None`, the default, returns authentication failures as backend
+    /// errors. Covers the operations listed under
+    /// [`with_timeout`](Self::with_timeout).
+    pub fn with_credentials(mut self, credentials: Option<Credentials>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Sign the client in again with the configured credentials.
+    async fn sign_in_again(&self, credentials: &Credentials) -> StoreResult<()> {
+        self.sign_in(credentials)
+            .await
+            .map_err(|e| SurrealStoreError::connection(format!("Signing in again failed: {e}")))
+    }
+
+    /// Sign the client in with `credentials`.
+    async fn sign_in(&self, credentials: &Credentials) -> surrealdb::Result<()> {
+        use surrealdb::opt::auth;
+        let signed_in = match credentials {
+            Credentials::Root { username, password } => {
+                self.client.signin(auth::Root { username, password }).await
+            }
+            Credentials::Namespace {
+                namespace,
+                username,
+                password,
+            } => {
+                self.client
+                    .signin(auth::Namespace {
+                        namespace,
+                        username,
+                        password,
+                    })
+                    .await
+            }
+            Credentials::Database {
+                namespace,
+                database,
+                username,
+                password,
+            } => {
+                self.client
+                    .signin(auth::Database {
+                        namespace,
+                        database,
+                        username,
+                        password,
+                    })
+                    .await
+            }
+        };
+        signed_in.map(|_| ())
+    }
+
+    /// Fail store operations that don't complete within `timeout` with a
+    /// backend error starting with [`SESSION_TIMEOUT`], so a hanging
+    /// database doesn't stall requests forever. Each attempt of an
+    /// operation is timed separately, so timeouts are retried and count
+    /// towards the circuit breaker like other backend errors. A write
+    /// that times out may still be applied. `None`, the default,
+    /// disables the timeout.
+    ///
+    /// The timeout, like the [retries](Self::with_retry), the
+    /// [circuit breaker](Self::with_circuit_breaker) and
+    /// [signing in again](Self::with_credentials), covers the methods of
+    /// [`SessionStore`] and [`ExpiredDeletion`] and the methods of the
+    /// store reading or writing sessions, each page of an export or a
+    /// migration being a separate operation. It doesn't cover setting up
+    /// the database, i.e. [`initialize`](Self::initialize) and the
+    /// `define_`, `ensure_` and `check_` methods,
+    /// [`list_session_tables`](Self::list_session_tables), or
+    /// [`load_consistent`](Self::load_consistent), whose statements can't
+    /// be run again.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Generate the new id of a created session whose id is taken with
+    /// `generator`, instead of a random id, e.g. to route sessions by a
+    /// prefix of their id. The generator is called again for as long as
+    /// the generated id is taken too, so it must eventually generate a
+    /// free one.
+    pub fn with_id_generator(mut self, generator: impl Fn() -> Id + Send + Sync + 'static) -> Self {
+        self.id_generator = Some(IdGenerator(Arc::new(generator)));
+        self
+    }
+
+    /// Check every session with `validator` before it is written, e.g.
+    /// that handlers only store the keys of a declared schema, which the
+    /// database can't check since the data is stored encoded. A session
+    /// the validator rejects with a message isn't written, and the write
+    /// fails with an encode error starting with [`SESSION_INVALID`]
+    /// followed by the message, a [`SurrealStoreError::Validation`] from
+    /// the store's own methods. Sessions already stored are not checked
+    /// when loaded.
+    pub fn with_validator(
+        mut self,
+        validator: impl Fn(&Record) -> std::result::Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.validator = Some(Validator(Arc::new(validator)));
+        self
+    }
+
+    /// Report counts and latencies of store operations to `metrics`.
+    /// Sessions created, saved, loaded, deleted and deleted by cleanups
+    /// through [`SessionStore`] and [`ExpiredDeletion`] are counted, and
+    /// the latency of each such operation is reported, including
+    /// retries. `None`, the default, reports nothing.
+    pub fn with_metrics(mut self, metrics: Option<Arc<dyn SessionMetrics>>) -> Self {
+        self.metrics = metrics.map(Metrics);
+        self
+    }
+
+    /// Report a completed operation to the metrics, if enabled.
+    fn report(&self, report: impl FnOnce(&dyn SessionMetrics)) {
+        if let Some(metrics) = &self.metrics {
+            report(metrics.0.as_ref());
+        }
+    }
+
+    /// Whether [`initialize`](Self::initialize) fails if the session
+    /// table doesn't exist. Useful when the table is managed outside the
+    /// application, e.g. in strict mode, to fail at startup rather than
+    /// on the first request. Defaults to `false`.
+    pub fn require_existing_table(mut self, require_existing_table: bool) -> Self {
+        self.require_existing_table = require_existing_table;
+        self
+    }
+
+    /// Warn in [`initialize`](Self::initialize) if the clocks of the
+    /// database and the application differ by more than `threshold`,
+    /// see [`check_clock_skew`](Self::check_clock_skew). `None`, the
+    /// default, skips the check.
+    pub fn with_clock_skew_warning(mut self, threshold: Option<Duration>) -> Self {
+        self.clock_skew_warning = threshold;
+        self
+    }
+
+    /// Run the startup checks enabled on the store, such as
+    /// [`require_existing_table`](Self::require_existing_table) and
+    /// [`with_clock_skew_warning`](Self::with_clock_skew_warning). Call
+    /// this once before serving requests. The server is always checked
+    /// to be at least [`MIN_SURREALDB_MAJOR_VERSION`].
+    pub async fn initialize(&self) -> StoreResult<()> {
+        let version = self
+            .client
+            .version()
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        check_server_version(version.major, &version)?;
+
+        if let Some(threshold) = self.clock_skew_warning {
+            let skew = self.check_clock_skew().await?;
+            if skew.unsigned_abs() > threshold {
+                let direction = if skew.is_negative() {
+                    "behind"
+                } else {
+                    "ahead of"
+                };
+                warn!(
+                    "Database clock is {} {direction} the application clock, sessions may expire early or late",
+                    skew.abs()
+                );
+            }
+        }
+        if self.require_existing_table
+            && !self
+                .database_info()
+                .await?
+                .tables
+                .contains_key(&self.session_table)
+        {
+            return Err(SurrealStoreError::not_found(format!(
+                "Session table '{}' does not exist",
+                self.session_table
+            )));
+        }
+        Ok(())
+    }
+
+    /// Compare the clock of the database with that of the application,
+    /// returning how far the database clock is ahead, or behind if
+    /// negative. Expiry dates are set from the application clock but
+    /// compared with the database clock, e.g. when loading, so sessions
+    /// expire early or late by the skew. The database clock is compared
+    /// with the application clock halfway through the query, so the
+    /// result is off by at most half the round trip.
+    pub async fn check_clock_skew(&self) -> StoreResult<time::Duration> {
+        let sent = OffsetDateTime::now_utc();
+        let started = Instant::now();
+        let database_now: Option<i64> = self
+            .query("return time::nano(time::now())")
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        let application_now = sent + started.elapsed() / 2;
+        let database_now =
+            database_now.ok_or_else(|| Error::Backend("No database time returned".to_string()))?;
+        let database_now = OffsetDateTime::from_unix_timestamp_nanos(database_now.into())
+            .map_err(|e| Error::Decode(e.to_string()))?;
+        Ok(database_now - application_now)
+    }
+
+    /// Check which operations the signed in user may run on the session
+    /// table, to debug table `PERMISSIONS` clauses. SurrealDB silently
+    /// skips records the user has no permission for, so e.g. a session
+    /// hidden from the user loads as if it had expired.
+    ///
+    /// The check creates, reads, updates and deletes an expired probe
+    /// session. Results of writes are only returned with select
+    /// permission, so without it every operation is reported as denied,
+    /// and updates and deletes are reported as denied if the probe
+    /// can't be created. A probe that can't be deleted is left for
+    /// [`delete_expired`](ExpiredDeletion::delete_expired).
+    pub async fn check_table_access(&self) -> StoreResult<TableAccessReport> {
+        let probe = Record {
+            id: Id::default(),
+            data: HashMap::new(),
+            expiry_date: OffsetDateTime::UNIX_EPOCH,
+        };
+        let record = StoredSessionRecord {
+            record: self.encode(&probe).await?,
+            expiry_column: self.expiry_column.clone(),
+            user_id: None,
+            pinned: None,
+        };
+        let mut response = self
+            .query(format!(
+                "create type::thing($table, $id) content {content} return id;
+select id from type::thing($table, $id);
+update type::thing($table, $id) set {column} = {expiry} return id;
+delete type::thing($table, $id) return before;",
+                content = self.record_content("$record"),
+                column = self.expiry_column,
+                expiry = self.expiry_at("$expiry")
+            ))
+            .bind(("table", self.session_table.clone()))
+            .bind(("id", self.record_key(&probe.id)))
+            .bind(("record", record))
+            .bind(("expiry", 1))
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        let mut permitted = |statement: usize| -> StoreResult<bool> {
+            let probe: Option<BTreeMap<String, IgnoredAny>> = response
+                .take(self.first_statement() + statement)
+                .map_err(read_error)?;
+            Ok(probe.is_some())
+        };
+        Ok(TableAccessReport {
+            create: permitted(0)?,
+            select: permitted(1)?,
+            update: permitted(2)?,
+            delete: permitted(3)?,
+        })
+    }
+
+    async fn database_info(&self) -> StoreResult<DatabaseInfo> {
+        let info: Option<DatabaseInfo> = self
+            .query("info for db")
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        info.ok_or_else(|| SurrealStoreError::connection("No database info returned"))
+    }
+
+    /// Record every create, save and delete in the table `audit_table`,
+    /// as a row with the `operation`, the `session` record key and a
+    /// `timestamp`. The audit row is written in the same transaction as
+    /// the session, so a session is never changed without an audit row
+    /// and an audit failure fails the operation. This adds a write to
+    /// each operation and makes it a multi-statement query.
+    pub fn with_audit_table(mut self, audit_table: Option<String>) -> Self {
+        self.audit_table = audit_table;
+        self
+    }
+
+    /// Build a query running `statement` on the session with record key
+    /// `key` together with an insert into `audit_table`, in one
+    /// transaction.
+    fn audited_query(
+        &self,
+        statement: &str,
+        audit_table: &str,
+        operation: &'static str,
+        key: String,
+    ) -> surrealdb::method::Query<'_, DB> {
+        self.query(format!(
+            "begin transaction;
+{statement};
+create type::table($audit_table) content {{
+    operation: $operation, session: $id, timestamp: time::now()
+}};
+commit transaction;"
+        ))
+        .bind(("table", self.session_table.clone()))
+        .bind(("id", key))
+        .bind(("audit_table", audit_table.to_string()))
+        .bind(("operation", operation))
+    }
+
+    /// Upsert a session, auditing it as `operation` if enabled.
+    async fn upsert_session(&self, session: &Record, operation: &'static str) -> StoreResult<()> {
+        self.write_session(session, "upsert", operation, None).await
+    }
+
+    /// Write a session with the `write` statement, `create` or `upsert`,
+    /// auditing it as `operation` if enabled, and pinning or unpinning
+    /// it if `pinned` is given.
+    async fn write_session(
+        &self,
+        session: &Record,
+        write: &str,
+        operation: &'static str,
+        pinned: Option<bool>,
+    ) -> StoreResult<()> {
+        let statement = format!(
+            "{write} type::thing($table, $id) content {}",
+            self.record_content("$record")
+        );
+        let key = self.record_key(&session.id);
+        let record = self.encode(session).await?;
+        if write == "upsert"
+            && pinned.is_none()
+            && self.skip_unchanged_saves
+            && self.select_record_matching(&session.id, "").await?.as_ref() == Some(&record)
+        {
+            return Ok(());
+        }
+        let expiry_date = record.expiry_date;
+        let record = StoredSessionRecord {
+            record,
+            expiry_column: self.expiry_column.clone(),
+            user_id: self.user_id(session),
+            pinned,
+        };
+        let saved = self
+            .write_record(statement, key.clone(), record, operation)
+            .await
+            .map_err(|e| {
+                e.map_backend(|e| {
+                    format!(
+                        "Writing session record in table {} failed: {}",
+                        self.session_table,
+                        redact_id(e, &session.id)
+                    )
+                })
+            })?;
+
+        match saved {
+            Some(saved) => {
+                SessionRecord::check_fields(&saved, &self.data_column, &self.expiry_column)
+            }
+            None => self.confirm_saved(key, expiry_date).await,
+        }
+    }
+
+    /// Run the write `statement` of `record` under the key `key`,
+    /// returning the written record if the database returns it.
+    async fn write_record(
+        &self,
+        statement: String,
+        key: String,
+        record: StoredSessionRecord,
+        operation: &'static str,
+    ) -> StoreResult<Option<BTreeMap<String, IgnoredAny>>> {
+        match &self.audit_table {
+            Some(audit_table) => audit_errors(
+                self.audited_query(&statement, audit_table, operation, key)
+                    .bind(("record", record))
+                    .await
+                    .map_err(write_error)?,
+            )?
+            .take(self.first_statement())
+            .map_err(read_error),
+            None => self
+                .query(statement)
+                .bind(("table", self.session_table.clone()))
+                .bind(("id", key))
+                .bind(("record", record))
+                .await
+                .map_err(write_error)?
+                .take(self.first_statement())
+                .map_err(read_error),
+        }
+    }
+
+    /// Confirm that a write was applied, by reading back the record with
+    /// key `key` and checking it has the expiry date written. Some
+    /// engines and configurations don't return the written record even
+    /// though the write succeeded.
+    async fn confirm_saved(&self, key: String, expiry_date: i64) -> StoreResult<()> {
+        let stored: Option<i64> = self
+            .query(format!(
+                "select value {} from type::thing($table, $id)",
+                self.unix_expiry()
+            ))
+            .bind(("table", self.session_table.clone()))
+            .bind(("id", key))
+            .await
+            .map_err(read_error)?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        if stored == Some(expiry_date) {
+            Ok(())
+        } else {
+            Err(SurrealStoreError::not_found(format!(
+                "{SESSION_NOT_SAVED}: the session was not found in table {} reading it back after the write, e.g. because permissions or an event hide or remove it",
+                self.session_table
+            )))
+        }
+    }
+
+    /// Save a session like [`save`](SessionStore::save), then read it
+    /// back in a separate query before returning, failing with a
+    /// backend error starting with [`SESSION_NOT_SAVED`] unless the
+    /// stored session has the expiry date written.
+    ///
+    /// This matters with remote engines behind a pool of connections,
+    /// where a following `load` may run on another connection than the
+    /// save: once this returns, the session was committed and is visible
+    /// to reads on any connection, and a session that permissions or
+    /// events hide or remove right after the write is reported instead
+    /// of silently missing on the next load. With the embedded engines
+    /// a save is always visible to the next load, so this only costs an
+    /// extra read.
+    pub async fn save_and_confirm(&self, session: &Record) -> StoreResult<()> {
+        let key = &self.record_key(&session.id);
+        let expiry_date = session.expiry_date.unix_timestamp();
+        self.guarded(|| async move {
+            self.upsert_session(session, "save").await?;
+            self.confirm_saved(key.clone(), expiry_date).await
+        })
+        .await
+    }
+
+    /// Save a session like [`save`](SessionStore::save), pinning it if
+    /// `pinned` or unpinning it otherwise, in the [`PINNED_COLUMN`]. A
+    /// pinned session is never deleted by the cleanup of expired
+    /// sessions, i.e. [`delete_expired`](ExpiredDeletion::delete_expired),
+    /// [`delete_expired_parallel`](Self::delete_expired_parallel) and the
+    /// [expiry event](Self::define_expiry_event), e.g. to keep "remember
+    /// me" sessions around. It is still deleted by
+    /// [`delete_expired_before`](Self::delete_expired_before) and
+    /// [`delete_all`](Self::delete_all), and, like any session, doesn't
+    /// load once expired until it is saved with a later expiry date.
+    ///
+    /// Saving a session any other way keeps it pinned or not.
+    pub async fn save_pinned(&self, session: &Record, pinned: bool) -> StoreResult<()> {
+        self.traced(
+            "save",
+            &session.id,
+            self.guarded(|| self.write_session(session, "upsert", "save", Some(pinned))),
+        )
+        .await
+    }
+
+    /// Create a session under its own id, failing with
+    /// [`SurrealStoreError::AlreadyExists`], whose message is
+    /// [`SESSION_EXISTS`], if the id is taken, rather
+    /// than moving the session to a new id like
+    /// [`create`](SessionStore::create). Use this when ids are assigned
+    /// by the application. An expired session not deleted yet still
+    /// takes its id.
+    pub async fn create_strict(&self, session: &Record) -> StoreResult<()> {
+        self.traced("create", &session.id, async {
+            if self.guarded(|| self.create_record_strict(session)).await? {
+                Ok(())
+            } else {
+                Err(SurrealStoreError::already_exists(SESSION_EXISTS))
+            }
+        })
+        .await?;
+        self.report(|metrics| metrics.session_created());
+        Ok(())
+    }
+
+    /// Delete a session like [`delete`](SessionStore::delete), returning
+    /// whether it existed, e.g. to audit only real logouts. A missing
+    /// session returns `false` rather than an error, regardless of
+    /// [`with_error_on_missing_delete`](Self::with_error_on_missing_delete).
+    pub async fn delete_returning(&self, session_id: &Id) -> StoreResult<bool> {
+        self.traced(
+            "delete",
+            session_id,
+            self.guarded(|| self.delete_record(session_id)),
+        )
+        .await
+    }
+
+    /// Whether deleting a session that doesn't exist is an error. By
+    /// default it is not, making deletes idempotent. Backend failures
+    /// such as a lost connection or missing permissions are errors
+    /// either way.
+    pub fn with_error_on_missing_delete(mut self, error_on_missing_delete: bool) -> Self {
+        self.error_on_missing_delete = error_on_missing_delete;
+        self
+    }
+
+    /// Store sessions under record keys starting with `prefix`, so
+    /// several applications can share a table. See
+    /// [`with_key_separator`](Self::with_key_separator) for the format
+    /// of prefixed keys. Existing sessions can be moved to a new prefix
+    /// with [`rekey_prefix`](Self::rekey_prefix). Record keys are always
+    /// bound as query parameters, so the prefix may contain any
+    /// characters.
+    pub fn with_key_prefix(mut self, prefix: Option<String>) -> Self {
+        self.key_prefix = prefix;
+        self
+    }
+
+    /// Place `separator` between the key prefix and the session id,
+    /// e.g. `sessions:⟨app_id⟩` with the default separator of `_`, or
+    /// `sessions:⟨appid⟩` with an empty separator. Has no effect if no
+    /// key prefix is set.
+    pub fn with_key_separator(mut self, separator: String) -> Self {
+        self.key_separator = separator;
+        self
+    }
+
+    /// Record key under which the session with the given id is stored,
+    /// i.e. the `id` part of the record id `table:id`. This is the
+    /// session id, preceded by the key prefix and separator if a prefix
+    /// is set. Keys must only reach queries as bound parameters, e.g.
+    /// `type::thing($table, $id)`, never formatted into the query text.
+    fn record_key(&self, session_id: &Id) -> String {
+        match &self.key_prefix {
+            Some(prefix) => format!("{prefix}{}{session_id}", self.key_separator),
+            None => session_id.to_string(),
+        }
+    }
+
+    /// Encode and decode sessions of at least `threshold` bytes on
+    /// Tokio's blocking thread pool instead of the async task, so large
+    /// sessions don't stall the runtime. Smaller sessions are still
+    /// handled inline, since moving work to the pool has a cost of its
+    /// own. The size of a session being encoded is estimated from its
+    /// data rather than measured. `None`, the default, handles all
+    /// sessions inline.
+    pub fn with_blocking_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.blocking_threshold = threshold;
+        self
+    }
+
+    /// Format sessions are written in: the configured format, except
+    /// that native data is written as JSON when encrypted.
+    fn write_format(&self) -> SerializationFormat {
+        match self.format {
+            _ if self.codec.is_some() => SerializationFormat::MessagePack,
+            SerializationFormat::Native if self.encrypted() => SerializationFormat::Json,
+            format => format,
+        }
+    }
+
+    async fn encode(&self, session: &Record) -> StoreResult<SessionRecord> {
+        if let Some(validator) = &self.validator {
+            (validator.0)(session).map_err(|message| {
+                SurrealStoreError::Validation(Error::Encode(format!(
+                    "{SESSION_INVALID}: {message}"
+                )))
+            })?;
+        }
+        let format = self.write_format();
+
+        let encoding = self.message_pack_encoding;
+        let record = match self.blocking_threshold {
+            Some(threshold) if estimated_size(session) >= threshold => {
+                let session = session.clone();
+                let codec = self.codec.clone();
+                tokio::task::spawn_blocking(move || {
+                    let codec = codec.as_ref().map(|codec| codec.0.as_ref());
+                    SessionRecord::from_session(&session, format, encoding, codec)
+                })
+                .await
+                .map_err(|e| Error::Backend(e.to_string()))??
+            }
+            _ => SessionRecord::from_session(session, format, encoding, self.codec())?,
+        };
+        let record = self.seal(&session.id, record)?;
+        self.check_data_size(&record)?;
+        Ok(record)
+    }
+
+    /// Compress session data with DEFLATE at `level`, from 0 (none) to 9
+    /// (best), before storing it, available with the `compression`
+    /// feature. `None`, the default, stores data uncompressed.
+    ///
+    /// Compressed data is stored as bytes, even in the JSON format, and
+    /// [`SerializationFormat::Native`] data is never compressed.
+    /// Uncompressed sessions stored earlier still load, so compression
+    /// can be enabled on an existing table.
+    #[cfg(feature = "compression")]
+    pub fn with_compression(mut self, level: Option<u32>) -> Self {
+        self.compression = level;
+        self
+    }
+
+    /// Whether session data is encrypted before it is stored.
+    fn encrypted(&self) -> bool {
+        #[cfg(feature = "encryption")]
+        return self.cipher.is_some();
+        #[cfg(not(feature = "encryption"))]
+        false
+    }
+
+    /// Compress and then encrypt the encoded data of a session, if
+    /// enabled. The data is encrypted for the record of the session, so
+    /// it can't be moved to the record of another session.
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    fn seal(&self, session_id: &Id, record: SessionRecord) -> StoreResult<SessionRecord> {
+        #[cfg(feature = "compression")]
+        let record = match self.compression {
+            Some(level) if !matches!(record.data, SessionData::Native(_)) => {
+                let data = SessionData::MessagePack(compression::compress(
+                    &record.data.as_bytes(),
+                    level,
+                )?);
+                SessionRecord { data, ..record }
+            }
+            _ => record,
+        };
+        #[cfg(feature = "encryption")]
+        let record = match &self.cipher {
+            Some(cipher) => {
+                let data = SessionData::MessagePack(
+                    cipher.encrypt(&record.data.as_bytes(), &self.associated_data(session_id))?,
+                );
+                SessionRecord { data, ..record }
+            }
+            None => record,
+        };
+        Ok(record)
+    }
+
+    /// Decrypt and then decompress the data of a stored session, the
+    /// inverse of [`seal`](Self::seal). Compressed data is recognized by
+    /// its header, so it is decompressed even if compression has since
+    /// been disabled.
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    fn open(&self, session_id: &Id, record: SessionRecord) -> StoreResult<SessionRecord> {
+        #[cfg(feature = "encryption")]
+        let record = match &self.cipher {
+            Some(cipher) => {
+                let SessionData::MessagePack(sealed) = &record.data else {
+                    return Err(SurrealStoreError::Serialization(Error::Decode(
+                        "Stored session is not encrypted".to_string(),
+                    )));
+                };
+                let data = SessionData::from_bytes(
+                    cipher.decrypt(sealed, &self.associated_data(session_id))?,
+                )?;
+                SessionRecord { data, ..record }
+            }
+            None => record,
+        };
+        #[cfg(feature = "compression")]
+        let record = match &record.data {
+            SessionData::MessagePack(bytes) => {
+                match compression::decompress(bytes, self.max_decode_size)? {
+                    Some(decompressed) => SessionRecord {
+                        data: SessionData::from_bytes(decompressed)?,
+                        ..record
+                    },
+                    None => record,
+                }
+            }
+            _ => record,
+        };
+        Ok(record)
+    }
+
+    /// Data authenticated along with the encrypted data of a session:
+    /// its record, as `table:key`.
+    #[cfg(feature = "encryption")]
+    fn associated_data(&self, session_id: &Id) -> Vec<u8> {
+        format!("{}:{}", self.session_table, self.record_key(session_id)).into_bytes()
+    }
+
+    async fn decode(&self, session_id: &Id, record: SessionRecord) -> StoreResult<Record> {
+        self.check_decode_size(&record)?;
+        let record = self.open(session_id, record)?;
+        match self.blocking_threshold {
+            Some(threshold) if record.data.len() >= threshold => {
+                let session_id = *session_id;
+                let codec = self.codec.clone();
+                tokio::task::spawn_blocking(move || {
+                    record.to_session(&session_id, codec.as_ref().map(|codec| codec.0.as_ref()))
+                })
+                .await
+                .map_err(|e| Error::Backend(e.to_string()))?
+            }
+            _ => record.to_session(session_id, self.codec()),
+        }
+    }
+
+    /// Parse the session id from a record key, the inverse of
+    /// [`record_key`](Self::record_key).
+    fn session_id_from_key(&self, key: &str) -> StoreResult<Id> {
+        let id = match &self.key_prefix {
+            Some(prefix) => key
+                .strip_prefix(prefix.as_str())
+                .and_then(|key| key.strip_prefix(self.key_separator.as_str()))
+                .ok_or_else(|| Error::Decode(format!("Unexpected session record key {key}")))?,
+            None => key,
+        };
+        id.parse().map_err(|e| {
+            SurrealStoreError::Serialization(Error::Decode(format!("Invalid session id {id}: {e}")))
+        })
+    }
+
+    /// Ping the database every `interval` in a background task, keeping
+    /// idle connections to remote engines from being dropped by
+    /// intermediaries. The task runs until the store and all of its
+    /// clones are dropped, and replaces any keep-alive set previously.
+    /// Passing `None` disables the keep-alive. Must be called from
+    /// within a Tokio runtime.
+    pub fn with_keep_alive(mut self, interval: Option<Duration>) -> Self {
+        self.keep_alive = interval.map(|interval| {
+            let client = self.client.clone();
+            Arc::new(KeepAlive(tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    ticker.tick().await;
+                    if let Err(e) = client.health().await {
+                        warn!("Session store keep-alive ping failed: {e}");
+                    }
+                }
+            })))
+        });
+        self
+    }
+
+    /// Delete expired sessions using up to `shards` concurrent delete
+    /// queries instead of the single query used by
+    /// [`ExpiredDeletion::delete_expired`].
+    ///
+    /// The range of expired `expiry_date` values is split into `shards`
+    /// equal ranges and each range is deleted by its own query. This can
+    /// shorten cleanup of very large tables on backends that execute
+    /// queries in parallel, but the queries compete for the same table
+    /// so more shards is not always faster, and on the in-memory and
+    /// single-node engines the deletes may simply be serialized. A
+    /// `shards` of 0 is treated as 1. Returns the total number of
+    /// sessions deleted, and each query is retried on its own if retries
+    /// are enabled.
+    pub async fn delete_expired_parallel(&self, shards: usize) -> StoreResult<u64> {
+        info!("Deleting expired sessions in {shards} shards");
+        let started = Instant::now();
+        let result = self.delete_expired_shards(shards).await;
+        self.report(|metrics| {
+            metrics.operation_completed("delete_expired", started.elapsed(), result.is_ok());
+            if let Ok(deleted) = &result {
+                metrics.expired_deleted(*deleted);
+            }
+        });
+        self.labeled("delete_expired", result)
+    }
+
+    async fn delete_expired_shards(&self, shards: usize) -> StoreResult<u64> {
+        let Some((oldest, now)) = self.guarded(|| self.expired_range()).await? else {
+            return Ok(0);
+        };
+        let shards = shards.max(1) as i64;
+        let width = (now - oldest) / shards + 1;
+        let deletes = (0..shards)
+            .map(|shard| oldest + shard * width)
+            .take_while(|lower| *lower <= now)
+            .map(|lower| {
+                let upper = (lower + width - 1).min(now);
+                self.guarded(move || self.delete_expiry_range(lower, upper))
+            });
+        let deleted = futures_util::future::try_join_all(deletes).await?;
+        Ok(deleted.into_iter().sum())
+    }
+
+    /// The unix expiry date of the oldest expired session and the
+    /// current time of the database, or `None` if no session expired.
+    async fn expired_range(&self) -> StoreResult<Option<(i64, i64)>> {
+        let mut response = self
+            .query(format!(
+                "select value {unix_expiry} from type::table($table)
+where {column} <= {now} order by {column} limit 1;
+return time::unix(time::now());",
+                column = self.expiry_column,
+                unix_expiry = self.unix_expiry(),
+                now = self.now()
+            ))
+            .bind(("table", self.session_table.clone()))
+            .await
+            .map_err(read_error)?;
+        let oldest: Option<i64> = response.take(self.first_statement()).map_err(read_error)?;
+        let now: Option<i64> = response
+            .take(self.first_statement() + 1)
+            .map_err(read_error)?;
+        Ok(oldest.zip(now))
+    }
+
+    /// Delete expired sessions in batches of at most `batch_size`, one
+    /// query per batch, until none remain, returning the total number of
+    /// sessions deleted. Unlike the single query of
+    /// [`ExpiredDeletion::delete_expired`], no query deletes more than a
+    /// batch, so cleanup of a very large table doesn't occupy the
+    /// database for long at a time. Each batch is retried on its own if
+    /// retries are enabled. Fails if `batch_size` is 0.
+    pub async fn delete_expired_batched(&self, batch_size: usize) -> StoreResult<u64> {
+        if batch_size == 0 {
+            return Err(SurrealStoreError::invalid_configuration(
+                "Batch size must not be 0",
+            ));
+        }
+        info!("Deleting expired sessions in batches of {batch_size}");
+        let started = Instant::now();
+        let mut total = 0;
+        let result = loop {
+            match self.guarded(|| self.delete_expired_batch(batch_size)).await {
+                Ok(deleted) => {
+                    total += deleted;
+                    if deleted < batch_size as u64 {
+                        break Ok(total);
+                    }
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        self.report(|metrics| {
+            metrics.operation_completed("delete_expired", started.elapsed(), result.is_ok());
+            metrics.expired_deleted(total);
+        });
+        self.labeled("delete_expired", result)
+    }
+
+    /// Move every session whose record key starts with `old_prefix` to
+    /// a key starting with `new_prefix` instead, returning the number
+    /// of sessions moved. Use this to keep sessions reachable after
+    /// changing how keys are prefixed; note that the prefixes given here
+    /// are matched against the whole record key, so they should include
+    /// the [key separator](Self::with_key_separator). Sessions are moved
+    /// with every stored field, such as their user id.
+    ///
+    /// Sessions are moved in batches, each in its own transaction, so an
+    /// error part way through leaves every session either fully moved or
+    /// untouched. Each batch is retried on its own if retries are
+    /// enabled, and sessions deleted while they are moved are not
+    /// counted. Fails without moving anything if `new_prefix` starts
+    /// with `old_prefix`, since moved keys would match again.
+    ///
+    /// Encrypted data is bound to the record it was written to, so
+    /// sessions of an [encrypting](Self::new_encrypted) store no longer
+    /// load once moved.
+    pub async fn rekey_prefix(&self, old_prefix: &str, new_prefix: &str) -> StoreResult<u64> {
+        if old_prefix == new_prefix {
+            return Ok(0);
+        }
+        if new_prefix.starts_with(old_prefix) {
+            return Err(SurrealStoreError::invalid_configuration(format!(
+                "Cannot rekey from prefix '{old_prefix}' to '{new_prefix}' which extends it"
+            )));
+        }
+
+        let mut moved = 0;
+        loop {
+            let batch = self
+                .guarded(|| self.rekey_batch(old_prefix, new_prefix))
+                .await;
+            match self.labeled("rekey_prefix", batch)? {
+                Some(batch_moved) => moved += batch_moved,
+                None => return Ok(moved),
+            }
+        }
+    }
+
+    /// Move a batch of sessions whose keys start with `old_prefix` to
+    /// keys starting with `new_prefix` in one transaction, returning the
+    /// number of sessions moved, or `None` if none are left to move.
+    /// Sessions deleted since their keys were listed are not moved or
+    /// counted.
+    async fn rekey_batch(&self, old_prefix: &str, new_prefix: &str) -> StoreResult<Option<u64>> {
+        let keys: Vec<String> = self
+            .query(
+                "select value record::id(id) from type::table($table)
+where string::starts_with(record::id(id), $prefix) limit $limit",
+            )
+            .bind(("table", self.session_table.clone()))
+            .bind(("prefix", old_prefix.to_string()))
+            .bind(("limit", REKEY_BATCH_SIZE))
+            .await
+            .map_err(read_error)?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        if keys.is_empty() {
+            return Ok(None);
+        }
+
+        let moves: Vec<KeyMove> = keys
+            .into_iter()
+            .map(|old| KeyMove {
+                new: format!("{new_prefix}{}", &old[old_prefix.len()..]),
+                old,
+            })
+            .collect();
+        let new_ids: Vec<surrealdb::RecordId> = moves
+            .iter()
+            .map(|key_move| {
+                surrealdb::RecordId::from_table_key(&self.session_table, key_move.new.clone())
+            })
+            .collect();
+        let moved: Vec<surrealdb::RecordId> = self
+            .query(
+                "begin transaction;
+for $move in $moves {
+    let $record = (select * omit id from only type::thing($table, $move.old));
+    if $record != none {
+        create type::thing($table, $move.new) content $record;
+        delete type::thing($table, $move.old);
+    };
+};
+select value id from $new_ids;
+commit transaction;",
+            )
+            .bind(("table", self.session_table.clone()))
+            .bind(("moves", moves))
+            .bind(("new_ids", new_ids))
+            .await
+            .map_err(write_error)?
+            .check()
+            .map_err(backend_error)?
+            .take(self.first_statement() + 1)
+            .map_err(read_error)?;
+        Ok(Some(moved.len() as u64))
+    }
+
+    /// List the tables in the current database holding sessions for
+    /// this store's naming convention: the store's own table, and any
+    /// table named after it followed by `_` and a suffix, such as
+    /// per-tenant tables `sessions_acme` and `sessions_globex` alongside
+    /// `sessions`. Useful for running cleanup or reporting across
+    /// dynamically created tables.
+    pub async fn list_session_tables(&self) -> StoreResult<Vec<String>> {
+        let tables = self.database_info().await?.tables;
+        let tenant_prefix = format!("{}_", self.session_table);
+        Ok(tables
+            .into_keys()
+            .filter(|table| *table == self.session_table || table.starts_with(&tenant_prefix))
+            .collect())
+    }
+
+    /// Stream every session in the table, including expired ones, for
+    /// export or migration. Sessions are fetched in pages of the
+    /// [page size](Self::with_page_size) ordered by record id, and the
+    /// next page is only fetched once the consumer has taken all
+    /// sessions of the previous one, so memory use is bounded regardless
+    /// of table size.
+    pub fn export_stream(&self) -> impl Stream<Item = StoreResult<(Id, Record)>> + '_ {
+        self.export_pages(ExportCursor::After(None))
+    }
+
+    /// Stream every session in the table like
+    /// [`export_stream`](Self::export_stream), but fetching the first
+    /// page before returning, so that e.g. a lost connection or missing
+    /// permissions fail the call rather than the first item. A session
+    /// that fails to decode is yielded as an `Err` item and the stream
+    /// continues with the next session.
+    pub async fn stream_all(
+        &self,
+    ) -> StoreResult<impl Stream<Item = StoreResult<(Id, Record)>> + '_> {
+        let rows = self.export_page(None).await?;
+        Ok(self.export_pages(ExportCursor::Fetched(rows)))
+    }
+
+    /// Stream the decoded sessions of the pages from `cursor` on,
+    /// fetching each page once the previous one is consumed.
+    fn export_pages(
+        &self,
+        cursor: ExportCursor,
+    ) -> impl Stream<Item = StoreResult<(Id, Record)>> + '_ {
+        futures_util::stream::try_unfold(cursor, move |cursor| async move {
+            let rows = match cursor {
+                ExportCursor::Fetched(rows) => rows,
+                ExportCursor::After(after) => self.export_page(after).await?,
+                ExportCursor::Done => return Ok::<_, SurrealStoreError>(None),
+            };
+            let next = match rows.last() {
+                Some(last) if rows.len() == self.page_size => {
+                    ExportCursor::After(Some(last.key.clone()))
+                }
+                _ => ExportCursor::Done,
+            };
+            Ok(Some((rows, next)))
+        })
+        .map_ok(|rows| {
+            futures_util::stream::iter(rows.into_iter().map(|row| -> StoreResult<(Id, Record)> {
+                self.check_decode_size(&row.record)?;
+                let session_id = self.session_id_from_key(&row.key)?;
+                let session = self
+                    .open(&session_id, row.record)?
+                    .to_session(&session_id, self.codec())?;
+                Ok((session.id, session))
+            }))
+        })
+        .try_flatten()
+    }
+
+    async fn export_page(&self, after: Option<String>) -> StoreResult<Vec<KeyedSessionRecord>> {
+        self.guarded(|| self.select_page(after.clone())).await
+    }
+
+    async fn select_page(&self, after: Option<String>) -> StoreResult<Vec<KeyedSessionRecord>> {
+        self.query(format!(
+            "select record::id(id) as key, {}, {} as expiry_date from type::table($table)
+where $after = none or id > type::thing($table, $after) order by id limit $limit",
+            self.data_field(),
+            self.unix_expiry()
+        ))
+        .bind(("table", self.session_table.clone()))
+        .bind(("after", after))
+        .bind(("limit", self.page_size))
+        .await
+        .map_err(read_error)?
+        .take(self.first_statement())
+        .map_err(read_error)
+    }
+
+    /// Rewrite every session stored in an older encoding, or in another
+    /// [`SerializationFormat`] than the configured one, in the current
+    /// encoding, so that sessions from before an upgrade keep working
+    /// without relying on [`with_migrate_on_read`](Self::with_migrate_on_read).
+    /// Sessions are scanned in pages like in
+    /// [`export_stream`](Self::export_stream), including expired ones,
+    /// and a session changed concurrently is left as is. Sessions are
+    /// not recompressed or reencrypted, and sessions of other stores
+    /// sharing the table under another key prefix are ignored.
+    ///
+    /// A session that fails to decode or be rewritten doesn't stop the
+    /// migration, and is listed in the returned report instead.
+    pub async fn migrate_records(&self) -> StoreResult<MigrationReport> {
+        let prefix = self
+            .key_prefix
+            .as_ref()
+            .map(|prefix| format!("{prefix}{}", self.key_separator));
+        let mut report = MigrationReport::default();
+        let mut after = None;
+        loop {
+            let rows = self.export_page(after.take()).await?;
+            if rows.len() == self.page_size {
+                after = rows.last().map(|row| row.key.clone());
+            }
+            for row in rows {
+                if prefix
+                    .as_ref()
+                    .is_some_and(|prefix| !row.key.starts_with(prefix.as_str()))
+                {
+                    continue;
+                }
+                let key = row.key.clone();
+                match self.migrate_row(row).await {
+                    Ok(true) => report.migrated += 1,
+                    Ok(false) => report.skipped += 1,
+                    Err(e) => {
+                        warn!("Failed to migrate session {key}: {e}");
+                        report.failed.push(key);
+                    }
+                }
+            }
+            if after.is_none() {
+                info!(
+                    "Migrated {} sessions, skipped {}, failed {}",
+                    report.migrated,
+                    report.skipped,
+                    report.failed.len()
+                );
+                return Ok(report);
+            }
+        }
+    }
+
+    /// Rewrite a session if it is in an older encoding or another
+    /// format, returning whether it was rewritten.
+    async fn migrate_row(&self, row: KeyedSessionRecord) -> StoreResult<bool> {
+        let session_id = self.session_id_from_key(&row.key)?;
+        let stored_data = row.record.data.clone();
+        self.check_decode_size(&row.record)?;
+        let record = self.open(&session_id, row.record)?;
+        if record.data.format() == self.write_format()
+            && (self.codec.is_some()
+                || !matches!(
+                    record.message_pack_encoding(),
+                    Some(encoding) if encoding != self.message_pack_encoding
+                ))
+        {
+            return Ok(false);
+        }
+        let session = record.to_session(&session_id, self.codec())?;
+        self.guarded(|| self.migrate_record(&session, stored_data.clone()))
+            .await?;
+        Ok(true)
+    }
+
+    /// Load all live sessions with the given ids in a single query, keyed
+    /// by id. Ids of sessions that don't exist or have expired are left
+    /// out of the result.
+    pub async fn load_many(&self, session_ids: &[Id]) -> StoreResult<HashMap<Id, Record>> {
+        let ids: Vec<surrealdb::RecordId> = session_ids
+            .iter()
+            .map(|id| surrealdb::RecordId::from_table_key(&self.session_table, self.record_key(id)))
+            .collect();
+        let rows = self.guarded(|| self.select_record_ids(ids.clone())).await?;
+        let mut sessions = HashMap::with_capacity(rows.len());
+        for row in rows {
+            let session_id = self.session_id_from_key(&row.key)?;
+            let session = self.decode(&session_id, row.record).await?;
+            sessions.insert(session_id, session);
+        }
+        Ok(sessions)
+    }
+
+    /// The ids of the live sessions of the user with id `user_id`, see
+    /// [`with_user_id_key`](Self::with_user_id_key). Sessions of other
+    /// stores sharing the table under another key prefix are left out.
+    pub async fn list_session_ids_for_user(&self, user_id: &str) -> StoreResult<Vec<Id>> {
+        self.require_user_ids()?;
+        let condition = format!("and {} > {}", self.expiry_column, self.now());
+        self.guarded(|| self.user_session_ids(user_id, &condition))
+            .await
+    }
+
+    /// Delete all sessions of the user with id `user_id`, including
+    /// expired ones, returning the number of sessions deleted, e.g. to
+    /// log a user out everywhere. See
+    /// [`with_user_id_key`](Self::with_user_id_key). Deleted sessions
+    /// are audited if enabled.
+    ///
+    /// The sessions are deleted by a single statement, so a session the
+    /// user creates while they are deleted is either deleted too or
+    /// created after the deletion, never missed in between.
+    pub async fn delete_by_user(&self, user_id: &str) -> StoreResult<u64> {
+        self.require_user_ids()?;
+        let result = self.guarded(|| self.delete_user_sessions(user_id)).await;
+        self.labeled("delete_by_user", result)
+    }
+
+    /// Delete the sessions of a user, skipping sessions of other key
+    /// prefixes, returning how many were deleted.
+    async fn delete_user_sessions(&self, user_id: &str) -> StoreResult<u64> {
+        let prefix = match &self.key_prefix {
+            Some(prefix) => format!("{prefix}{}", self.key_separator),
+            None => String::new(),
+        };
+        // Keys of other prefixes starting with this one are longer, since
+        // every session id has the same length.
+        let key_length = prefix.chars().count() + Id::default().to_string().len();
+        let delete = format!(
+            "delete type::table($table) where {USER_ID_COLUMN} = $user_id
+and string::starts_with(record::id(id), $prefix)
+and string::len(record::id(id)) = $key_length return before"
+        );
+        let (query, statement) = match &self.audit_table {
+            Some(audit_table) => (
+                self.query(format!(
+                    "begin transaction;
+let $deleted = ({delete});
+for $session in $deleted {{
+    create type::table($audit_table) content {{
+        operation: 'delete', session: record::id($session.id), timestamp: time::now()
+    }};
+}};
+select value id from $deleted;
+commit transaction;"
+                ))
+                .bind(("audit_table", audit_table.clone())),
+                // After the `let` and `for` statements.
+                self.first_statement() + 2,
+            ),
+            None => (self.query(delete), self.first_statement()),
+        };
+        let mut response = query
+            .bind(("table", self.session_table.clone()))
+            .bind(("user_id", user_id.to_string()))
+            .bind(("prefix", prefix))
+            .bind(("key_length", key_length))
+            .await
+            .map_err(write_error)?;
+        errors_to_result(response.take_errors(), "Deleting sessions of user failed")?;
+        let deleted: Vec<IgnoredAny> = response.take(statement).map_err(read_error)?;
+        Ok(deleted.len() as u64)
+    }
+
+    /// Fail unless sessions are stored with user ids.
+    fn require_user_ids(&self) -> StoreResult<()> {
+        match self.user_id_key {
+            Some(_) => Ok(()),
+            None => Err(SurrealStoreError::invalid_configuration(
+                "Sessions are not stored with user ids, see `with_user_id_key`",
+            )),
+        }
+    }
+
+    /// The ids of the sessions of a user matching the `condition`
+    /// clause, which continues the `where` clause, skipping sessions of
+    /// other key prefixes.
+    async fn user_session_ids(&self, user_id: &str, condition: &str) -> StoreResult<Vec<Id>> {
+        let prefix = match &self.key_prefix {
+            Some(prefix) => format!("{prefix}{}", self.key_separator),
+            None => String::new(),
+        };
+        let keys: Vec<String> = self
+            .query(format!(
+                "select value record::id(id) from type::table($table)
+where {USER_ID_COLUMN} = $user_id and string::starts_with(record::id(id), $prefix) {condition}"
+            ))
+            .bind(("table", self.session_table.clone()))
+            .bind(("user_id", user_id.to_string()))
+            .bind(("prefix", prefix))
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        Ok(keys
+            .iter()
+            .filter_map(|key| self.session_id_from_key(key).ok())
+            .collect())
+    }
+
+    /// Save all of `sessions` in a single transaction, so that either all
+    /// or none of them are written. Saved sessions are audited if
+    /// enabled.
+    pub async fn save_many(&self, sessions: &[Record]) -> StoreResult<()> {
+        if sessions.is_empty() {
+            return Ok(());
+        }
+        let mut rows = Vec::with_capacity(sessions.len());
+        for session in sessions {
+            rows.push(KeyedStoredSessionRecord {
+                key: self.record_key(&session.id),
+                record: StoredSessionRecord {
+                    record: self.encode(session).await?,
+                    expiry_column: self.expiry_column.clone(),
+                    user_id: self.user_id(session),
+                    pinned: None,
+                },
+            });
+        }
+        self.guarded(|| self.save_rows(rows.clone()))
+            .await
+            .map_err(|e| {
+                e.map_backend(|e| {
+                    sessions
+                        .iter()
+                        .fold(e, |e, session| redact_id(e, &session.id))
+                })
+            })
+    }
+
+    async fn save_rows(&self, rows: Vec<KeyedStoredSessionRecord>) -> StoreResult<()> {
+        let audit = match &self.audit_table {
+            Some(_) => {
+                "
+    create type::table($audit_table) content {
+        operation: 'save', session: $row.key, timestamp: time::now()
+    };"
+            }
+            None => "",
+        };
+        let mut response = self
+            .query(format!(
+                "begin transaction;
+for $row in $rows {{
+    upsert type::thing($table, $row.key) content {};{audit}
+}};
+commit transaction;",
+                self.record_content("$row.record")
+            ))
+            .bind(("table", self.session_table.clone()))
+            .bind(("audit_table", self.audit_table.clone()))
+            .bind(("rows", rows))
+            .await
+            .map_err(write_error)?;
+        errors_to_result(response.take_errors(), "Saving sessions failed")
+    }
+
+    /// Delete all sessions with the given ids in a single statement,
+    /// e.g. to invalidate a group of sessions at once. Ids of sessions
+    /// that don't exist are ignored, even when
+    /// [`with_error_on_missing_delete`](Self::with_error_on_missing_delete)
+    /// is enabled. Deleted sessions are audited if enabled.
+    pub async fn delete_many(&self, session_ids: &[Id]) -> StoreResult<()> {
+        let ids: Vec<surrealdb::RecordId> = session_ids
+            .iter()
+            .map(|id| surrealdb::RecordId::from_table_key(&self.session_table, self.record_key(id)))
+            .collect();
+        self.guarded(|| self.delete_record_ids(ids.clone())).await
+    }
+
+    /// Delete expired sessions like
+    /// [`delete_expired`](ExpiredDeletion::delete_expired), returning
+    /// the number of sessions deleted. The space of deleted sessions is
+    /// reclaimed by the storage engine in its own time, since SurrealDB
+    /// has no statement to compact storage.
+    pub async fn delete_expired_count(&self) -> StoreResult<u64> {
+        let started = Instant::now();
+        let result = self.guarded(|| self.delete_expired_records()).await;
+        self.report(|metrics| {
+            metrics.operation_completed("delete_expired", started.elapsed(), result.is_ok());
+            if let Ok(deleted) = &result {
+                metrics.expired_deleted(*deleted);
+            }
+        });
+        self.labeled("delete_expired", result)
+    }
+
+    /// Delete expired sessions like
+    /// [`delete_expired_count`](Self::delete_expired_count), returning
+    /// the number of sessions deleted.
+    ///
+    /// This only deletes: SurrealDB 2 has no statement to compact
+    /// storage on any engine, so the store can't reclaim space itself.
+    /// The in-memory engine frees it immediately, RocksDB and SurrealKV
+    /// reclaim it as they compact in the background, and TiKV and
+    /// FoundationDB compact on the server.
+    pub async fn delete_expired_and_compact(&self) -> StoreResult<u64> {
+        self.delete_expired_count().await
+    }
+
+    /// The ids of the sessions that
+    /// [`delete_expired`](ExpiredDeletion::delete_expired) would delete
+    /// if run now, without deleting them, e.g. to audit what a cleanup
+    /// would remove before enabling it. Pinned sessions are not listed.
+    /// Expired records whose keys aren't session ids of this store, such
+    /// as those of another [key prefix](Self::with_key_prefix), are
+    /// deleted by a cleanup too but not listed.
+    pub async fn expired_session_ids(&self) -> StoreResult<Vec<Id>> {
+        let keys: Vec<String> = self
+            .guarded(|| async {
+                self.query(format!(
+                    "select value record::id(id) from type::table($table)
+where {} <= {} and {PINNED_COLUMN} != true",
+                    self.expiry_column,
+                    self.now()
+                ))
+                .bind(("table", self.session_table.clone()))
+                .await
+                .map_err(|e| Error::Backend(e.to_string()))?
+                .take(self.first_statement())
+                .map_err(read_error)
+            })
+            .await?;
+        Ok(keys
+            .iter()
+            .filter_map(|key| self.session_id_from_key(key).ok())
+            .collect())
+    }
+
+    /// Delete every session in the table, expired or not, returning the
+    /// number of sessions deleted. This logs everyone out, including
+    /// users of other stores sharing the table under another key
+    /// prefix. Deleted sessions are not audited.
+    pub async fn delete_all(&self) -> StoreResult<u64> {
+        self.guarded(|| self.delete_records("")).await
+    }
+
+    /// Delete the sessions that expired at or before `cutoff`, returning
+    /// the number of sessions deleted. Unlike
+    /// [`delete_expired`](ExpiredDeletion::delete_expired), which uses
+    /// the current time, this purges by a fixed date, e.g. for data
+    /// retention. A cutoff in the future deletes live sessions too.
+    /// Deleted sessions are not audited.
+    pub async fn delete_expired_before(&self, cutoff: OffsetDateTime) -> StoreResult<u64> {
+        self.guarded(|| self.delete_expired_before_cutoff(cutoff.unix_timestamp()))
+            .await
+    }
+
+    /// Count the sessions that have not expired, without loading them.
+    pub async fn count_active(&self) -> StoreResult<u64> {
+        self.count_records(&format!("where {} > {}", self.expiry_column, self.now()))
+            .await
+    }
+
+    /// Count all sessions in the table, including expired sessions not
+    /// deleted yet.
+    pub async fn count_all(&self) -> StoreResult<u64> {
+        self.count_records("").await
+    }
+
+    /// Count the sessions matching the `condition` clause.
+    async fn count_records(&self, condition: &str) -> StoreResult<u64> {
+        let result = self.guarded(|| self.select_count(condition)).await;
+        self.labeled("count", result)
+    }
+
+    async fn select_count(&self, condition: &str) -> StoreResult<u64> {
+        let count: Option<u64> = self
+            .query(format!(
+                "select count() from type::table($table) {condition} group all"
+            ))
+            .bind(("table", self.session_table.clone()))
+            .await
+            .map_err(read_error)?
+            .take((self.first_statement(), "count"))
+            .map_err(read_error)?;
+        // An empty table has no group to count, so no row is returned.
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Whether a session exists and has not expired. Cheaper than
+    /// loading the session, since its data is not fetched or decoded.
+    pub async fn exists(&self, session_id: &Id) -> StoreResult<bool> {
+        let result = self.guarded(|| self.select_exists(session_id)).await;
+        self.labeled("exists", result)
+    }
+
+    async fn select_exists(&self, session_id: &Id) -> StoreResult<bool> {
+        let id: Option<surrealdb::RecordId> = self
+            .query(format!(
+                "select value id from type::thing($table, $id) where {} > {}",
+                self.expiry_column,
+                self.now()
+            ))
+            .bind(("id", self.record_key(session_id)))
+            .bind(("table", self.session_table.clone()))
+            .await
+            .map_err(read_error)?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        Ok(id.is_some())
+    }
+
+    /// Get the expiry date of a session, or `None` if it doesn't exist
+    /// or has expired. Cheaper than loading the session, since its data
+    /// is not fetched or decoded.
+    pub async fn get_expiry(&self, session_id: &Id) -> StoreResult<Option<OffsetDateTime>> {
+        let expiry_date = self.guarded(|| self.select_expiry(session_id)).await;
+        self.labeled("get_expiry", expiry_date)?
+            .map(|expiry_date| {
+                OffsetDateTime::from_unix_timestamp(expiry_date)
+                    .map_err(|e| SurrealStoreError::Serialization(Error::Decode(e.to_string())))
+            })
+            .transpose()
+    }
+
+    async fn select_expiry(&self, session_id: &Id) -> StoreResult<Option<i64>> {
+        self.query(format!(
+            "select value {unix_expiry} from type::thing($table, $id)
+where {column} > {now}",
+            column = self.expiry_column,
+            unix_expiry = self.unix_expiry(),
+            now = self.now()
+        ))
+        .bind(("id", self.record_key(session_id)))
+        .bind(("table", self.session_table.clone()))
+        .await
+        .map_err(read_error)?
+        .take(self.first_statement())
+        .map_err(read_error)
+    }
+
+    /// Load what is stored about a live session besides its data, or
+    /// `None` if it doesn't exist or has expired, e.g. to list sessions
+    /// in an admin view. Cheaper than loading the session, since its
+    /// data is not fetched or decoded.
+    pub async fn load_metadata(&self, session_id: &Id) -> StoreResult<Option<SessionMeta>> {
+        let record = self.guarded(|| self.select_metadata(session_id)).await;
+        let Some(record) = self.labeled("load_metadata", record)? else {
+            return Ok(None);
+        };
+        let expiry_date = OffsetDateTime::from_unix_timestamp(record.expiry_date)
+            .map_err(|e| SurrealStoreError::Serialization(Error::Decode(e.to_string())))?;
+        Ok(Some(SessionMeta {
+            expiry_date,
+            user_id: record.user_id,
+            pinned: record.pinned.unwrap_or_default(),
+        }))
+    }
+
+    async fn select_metadata(&self, session_id: &Id) -> StoreResult<Option<SessionMetaRecord>> {
+        self.query(format!(
+            "select {unix_expiry} as expiry_date, {USER_ID_COLUMN} as user_id,
+{PINNED_COLUMN} as pinned from type::thing($table, $id) where {column} > {now}",
+            column = self.expiry_column,
+            unix_expiry = self.unix_expiry(),
+            now = self.now()
+        ))
+        .bind(("id", self.record_key(session_id)))
+        .bind(("table", self.session_table.clone()))
+        .await
+        .map_err(read_error)?
+        .take(self.first_statement())
+        .map_err(read_error)
+    }
+
+    /// Get how long until a session expires, or `None` if it doesn't
+    /// exist or has expired.
+    pub async fn ttl_remaining(&self, session_id: &Id) -> StoreResult<Option<time::Duration>> {
+        let expiry_date = self.get_expiry(session_id).await?;
+        Ok(expiry_date
+            .map(|expiry_date| expiry_date - OffsetDateTime::now_utc())
+            .filter(|remaining| remaining.is_positive()))
+    }
+
+    /// Set the expiry date of a live session to `expiry_date` without
+    /// rewriting its data, e.g. to slide the expiry of a session that
+    /// was only read. Does nothing if the session doesn't exist or has
+    /// expired, so an expired session is never revived. The expiry date
+    /// is stored to the second. Touches are not audited.
+    pub async fn touch(&self, session_id: &Id, expiry_date: OffsetDateTime) -> StoreResult<()> {
+        self.guarded(|| self.touch_record(session_id, expiry_date))
+            .await
+    }
+
+    async fn touch_record(&self, session_id: &Id, expiry_date: OffsetDateTime) -> StoreResult<()> {
+        self.query(format!(
+            "update type::thing($table, $id) set {column} = {expiry_date}
+where {column} > {now}",
+            column = self.expiry_column,
+            expiry_date = self.expiry_at("$expiry_date"),
+            now = self.now()
+        ))
+        .bind(("table", self.session_table.clone()))
+        .bind(("id", self.record_key(session_id)))
+        .bind(("expiry_date", expiry_date.unix_timestamp()))
+        .await
+        .map_err(write_error)?
+        .check()
+        .map_err(|e| Error::Backend(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Load a live session and set its expiry date to `expiry_date` in
+    /// the same query, for sliding expiry without the extra round trip
+    /// of a [`touch`](Self::touch) or the race of loading then saving.
+    /// Returns the session with its new expiry date, or `None` if it
+    /// doesn't exist or has expired, so an expired session is never
+    /// revived. The expiry date is stored to the second.
+    pub async fn load_and_slide(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> StoreResult<Option<Record>> {
+        self.guarded(|| self.slide_record(session_id, expiry_date))
+            .await
+    }
+
+    async fn slide_record(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> StoreResult<Option<Record>> {
+        let record: Option<SessionRecord> = self
+            .query(format!(
+                "update type::thing($table, $id) set {column} = {expiry_date}
+where {column} > {now}
+return {unix_expiry} as expiry_date, {data}",
+                data = self.data_field(),
+                column = self.expiry_column,
+                expiry_date = self.expiry_at("$expiry_date"),
+                unix_expiry = self.unix_expiry(),
+                now = self.now()
+            ))
+            .bind(("table", self.session_table.clone()))
+            .bind(("id", self.record_key(session_id)))
+            .bind(("expiry_date", expiry_date.unix_timestamp()))
+            .await
+            .map_err(write_error)?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        match record {
+            Some(record) => Ok(Some(self.decode(session_id, record).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Replace the data of a live session, keeping its expiry date, and
+    /// return the updated session, or `None` if the session doesn't
+    /// exist or has expired. Unlike building a [`Record`] and calling
+    /// [`save`](SessionStore::save), the new data replaces the old
+    /// rather than being merged by the caller, and an expired or deleted
+    /// session is never recreated.
+    ///
+    /// The update only applies if the stored expiry date is unchanged
+    /// since the session was read, so `None` is also returned if the
+    /// expiry was changed concurrently.
+    pub async fn replace_data(
+        &self,
+        session_id: &Id,
+        data: HashMap<String, serde_json::Value>,
+    ) -> StoreResult<Option<Record>> {
+        let result = self
+            .guarded(|| self.replace_record_data(session_id, data.clone()))
+            .await;
+        self.labeled("replace_data", result)
+    }
+
+    async fn replace_record_data(
+        &self,
+        session_id: &Id,
+        data: HashMap<String, serde_json::Value>,
+    ) -> StoreResult<Option<Record>> {
+        let Some(current) = self.load_record(session_id).await? else {
+            return Ok(None);
+        };
+        let session = Record { data, ..current };
+        let record = self.encode(&session).await?;
+
+        let user_id = match self.user_id_key {
+            Some(_) => format!(", {USER_ID_COLUMN} = $user_id"),
+            None => String::new(),
+        };
+        let statement = format!(
+            "let $updated = (update type::thing($table, $id) set {data} = $data{user_id}
+where {column} = {expiry_date} and {column} > {now});
+if !$updated {{ throw $not_found }}",
+            data = self.data_column,
+            column = self.expiry_column,
+            expiry_date = self.expiry_at("$expiry_date"),
+            now = self.now()
+        );
+        let key = self.record_key(session_id);
+        let query = match &self.audit_table {
+            Some(audit_table) => self.audited_query(&statement, audit_table, "save", key),
+            None => self
+                .query(statement)
+                .bind(("table", self.session_table.clone()))
+                .bind(("id", key)),
+        };
+        let mut response = query
+            .bind(("data", record.data))
+            .bind(("user_id", self.user_id(&session)))
+            .bind(("expiry_date", record.expiry_date))
+            .bind(("not_found", SESSION_NOT_UPDATED))
+            .await
+            .map_err(write_error)?;
+        let errors = response.take_errors();
+        if errors
+            .values()
+            .any(|e| e.to_string().contains(SESSION_NOT_UPDATED))
+        {
+            return Ok(None);
+        }
+        errors_to_result(errors, "Replacing session data failed")?;
+        Ok(Some(session))
+    }
+
+    /// Load a session like [`load`](SessionStore::load) and run the
+    /// `related` statements in the same transaction, so that they see
+    /// the database in the same state as the load. `bind` can add
+    /// bindings for the statements; the bindings `$session_table` and
+    /// `$session_id` are used by the store.
+    ///
+    /// The results of the related statements are in the returned
+    /// response starting at index 1, or 2 with a
+    /// [scope](Self::with_scope).
+    pub async fn load_consistent(
+        &self,
+        session_id: &Id,
+        related: &str,
+        bind: impl FnOnce(surrealdb::method::Query<'_, DB>) -> surrealdb::method::Query<'_, DB>,
+    ) -> StoreResult<(Option<Record>, surrealdb::Response)> {
+        let query = self
+            .query(format!(
+                "begin transaction;
+select {unix_expiry} as expiry_date, {data} from type::thing($session_table, $session_id)
+where {column} > {now};
+{related};
+commit transaction;",
+                data = self.data_field(),
+                column = self.expiry_column,
+                unix_expiry = self.unix_expiry(),
+                now = self.now()
+            ))
+            .bind(("session_table", self.session_table.clone()))
+            .bind(("session_id", self.record_key(session_id)));
+        let mut response = bind(query)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        let record: Option<SessionRecord> =
+            response.take(self.first_statement()).map_err(read_error)?;
+        let session = match record {
+            Some(record) => Some(self.decode(session_id, record).await?),
+            None => None,
+        };
+        Ok((session, response))
+    }
+
+    /// Load a session like [`load`](SessionStore::load), along with the
+    /// stored bytes it was decoded from. The bytes can e.g. be hashed to
+    /// detect changes to the session without encoding it again.
+    pub async fn load_with_raw(&self, session_id: &Id) -> StoreResult<Option<(Record, Vec<u8>)>> {
+        let record = self.guarded(|| self.select_record(session_id)).await;
+        let Some(record) = self.labeled("load_with_raw", record)? else {
+            return Ok(None);
+        };
+        let raw = record.data.as_bytes().into_owned();
+        let session = self.decode(session_id, record).await?;
+        Ok(Some((session, raw)))
+    }
+
+    /// Load the stored data of a session as is, along with its stored
+    /// expiry date, without decoding it. This is a debugging aid, e.g.
+    /// to inspect a session that fails to decode after a change of
+    /// format or codec; use [`load`](SessionStore::load) otherwise.
+    ///
+    /// Expired sessions not deleted yet are returned too. The data is
+    /// returned as stored, so still compressed and encrypted if enabled,
+    /// and data stored as a native object is returned as JSON.
+    pub async fn load_raw(
+        &self,
+        session_id: &Id,
+    ) -> StoreResult<Option<(Vec<u8>, OffsetDateTime)>> {
+        let record = self
+            .guarded(|| self.select_record_matching(session_id, ""))
+            .await;
+        let Some(record) = self.labeled("load_raw", record)? else {
+            return Ok(None);
+        };
+        let expiry_date = OffsetDateTime::from_unix_timestamp(record.expiry_date)
+            .map_err(|e| SurrealStoreError::Serialization(Error::Decode(e.to_string())))?;
+        Ok(Some((record.data.as_bytes().into_owned(), expiry_date)))
+    }
+
+    /// Load a session like [`load`](SessionStore::load), but also if it
+    /// has expired and not been deleted yet. This is a diagnostic aid,
+    /// e.g. to inspect the session of a user who was logged out; it
+    /// bypasses expiry, so never use it to authenticate or authorize
+    /// requests.
+    pub async fn load_including_expired(&self, session_id: &Id) -> StoreResult<Option<Record>> {
+        let record = self
+            .guarded(|| self.select_record_matching(session_id, ""))
+            .await;
+        let Some(record) = self.labeled("load_including_expired", record)? else {
+            return Ok(None);
+        };
+        Ok(Some(self.decode(session_id, record).await?))
+    }
+
+    /// Continuously delete expired sessions from each of `tables`, each
+    /// on its own schedule, in tasks managed by the returned set. Each
+    /// table is cleaned every [`CleanupIntervals::for_table`], which is
+    /// the shared default interval unless the table has its own. The
+    /// tables can e.g. come from
+    /// [`list_session_tables`](Self::list_session_tables). A task ends
+    /// if its cleanup fails, and dropping the set stops all tasks. Must
+    /// be called from within a Tokio runtime.
+    pub fn spawn_cleanup_per_table(
+        &self,
+        tables: impl IntoIterator<Item = String>,
+        intervals: &CleanupIntervals,
+    ) -> tokio::task::JoinSet<session_store::Result<()>> {
+        let mut tasks = tokio::task::JoinSet::new();
+        for table in tables {
+            let period = intervals.for_table(&table);
+            let store = Self {
+                session_table: table,
+                ..self.clone()
+            };
+            tasks.spawn(store.continuously_delete_expired(period));
+        }
+        tasks
+    }
+
+    /// Spawn a task continuously deleting expired sessions every
+    /// `period`, like
+    /// [`continuously_delete_expired`](ExpiredDeletion::continuously_delete_expired),
+    /// returning its handle, e.g. to abort it on shutdown or to observe
+    /// it panicking. The task logs a failed cleanup and ends. Must be
+    /// called from within a Tokio runtime.
+    pub fn spawn_cleanup(self, period: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::task::spawn(self.cleanup_task(period))
+    }
+
+    /// Spawn a task continuously deleting expired sessions like
+    /// [`spawn_cleanup`](Self::spawn_cleanup), but in `tasks`, so it is
+    /// awaited and aborted along with the other tasks of the set.
+    /// Returns a handle to abort just this task. Must be called from
+    /// within a Tokio runtime.
+    pub fn spawn_cleanup_in(
+        self,
+        period: Duration,
+        tasks: &mut tokio::task::JoinSet<()>,
+    ) -> tokio::task::AbortHandle {
+        tasks.spawn(self.cleanup_task(period))
+    }
+
+    async fn cleanup_task(self, period: Duration) {
+        if let Err(e) = self.continuously_delete_expired(period).await {
+            warn!("Session cleanup failed, stopping: {e}");
+        }
+    }
+
+    /// Continuously delete expired sessions every `period`, like
+    /// [`continuously_delete_expired`](ExpiredDeletion::continuously_delete_expired),
+    /// until `shutdown` completes. A cleanup already running when
+    /// `shutdown` completes is finished, after which expired sessions
+    /// are deleted one final time before returning. Fails as soon as a
+    /// cleanup fails.
+    pub async fn continuously_delete_expired_with_shutdown(
+        self,
+        period: Duration,
+        shutdown: impl Future<Output = ()>,
+    ) -> StoreResult<()> {
+        let mut interval = tokio::time::interval(period);
+        // The first tick completes immediately; skip.
+        interval.tick().await;
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => self.delete_expired().await?,
+                _ = &mut shutdown => break,
+            }
+        }
+        info!("Shutting down session cleanup");
+        self.delete_expired().await.map_err(SurrealStoreError::from)
+    }
+
+    /// Continuously delete expired sessions like
+    /// [`continuously_delete_expired`](ExpiredDeletion::continuously_delete_expired),
+    /// but waiting a random time between `period` shortened and
+    /// lengthened by `jitter_fraction` of it before each cleanup, e.g.
+    /// between 54 and 66 seconds for a period of a minute and the
+    /// [`DEFAULT_CLEANUP_JITTER`] of 0.1. This spreads out the cleanups
+    /// of many instances started at the same time. The fraction is
+    /// clamped between 0 and 1. Fails as soon as a cleanup fails.
+    pub async fn continuously_delete_expired_with_jitter(
+        self,
+        period: Duration,
+        jitter_fraction: f64,
+    ) -> StoreResult<()> {
+        loop {
+            tokio::time::sleep(jittered(period, jitter_fraction, rand::random())).await;
+            self.delete_expired().await?;
+        }
+    }
+
+    /// Continuously delete expired sessions like
+    /// [`continuously_delete_expired`](ExpiredDeletion::continuously_delete_expired),
+    /// passing the number of sessions deleted by each cleanup, or its
+    /// error, to `callback`, e.g. to alert on repeated failures. Unlike
+    /// `continuously_delete_expired`, a failed cleanup doesn't stop the
+    /// loop, so this never returns.
+    pub async fn continuously_delete_expired_with_callback(
+        self,
+        period: Duration,
+        mut callback: impl FnMut(StoreResult<u64>),
+    ) {
+        let mut interval = tokio::time::interval(period);
+        // The first tick completes immediately; skip.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            callback(self.delete_expired_count().await);
+        }
+    }
+
+    /// Run a store operation, subject to the circuit breaker, retried on
+    /// backend errors and after signing in again on authentication
+    /// failures if enabled. `operation` is called again for each attempt.
+    async fn guarded<T, F>(&self, mut operation: impl FnMut() -> F) -> StoreResult<T>
+    where
+        F: Future<Output = StoreResult<T>>,
+    {
+        let mut attempt = 1;
+        let mut signed_in_again = false;
+        loop {
+            let result = self.breaker_guarded(self.timed(operation())).await;
+            match (&self.credentials, &self.retry, &result) {
+                (Some(credentials), _, Err(e))
+                    if !signed_in_again
+                        && matches!(e.inner(), Error::Backend(message)
+                            if AUTH_FAILURES.iter().any(|failure| message.contains(failure))) =>
+                {
+                    warn!("Session store authentication failed, signing in again: {e}");
+                    self.sign_in_again(credentials).await?;
+                    signed_in_again = true;
+                }
+                (_, Some(policy), Err(e))
+                    if attempt < policy.max_attempts && matches!(e.inner(), Error::Backend(_)) =>
+                {
+                    let delay = policy.delay(attempt);
+                    warn!("Session store operation failed, retrying in {delay:?}: {e}");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    /// Run a store operation in a `session_store` span recording the
+    /// operation, the session table, the elapsed time and any error,
+    /// report its latency to the metrics and label its backend errors.
+    /// The session id is a credential, so it is only recorded when debug
+    /// level is enabled; session data is never recorded.
+    async fn traced<T>(
+        &self,
+        operation: &'static str,
+        session_id: &Id,
+        future: impl Future<Output = StoreResult<T>>,
+    ) -> StoreResult<T> {
+        let span = info_span!(
+            "session_store",
+            operation,
+            table = %self.session_table,
+            session_id = field::Empty,
+            elapsed_ms = field::Empty,
+            error = field::Empty,
+            // Recorded with the `otel` feature.
+            otel.name = field::Empty,
+            otel.kind = field::Empty,
+            otel.status_code = field::Empty,
+            db.system = field::Empty,
+            db.operation.name = field::Empty,
+            db.collection.name = field::Empty,
+            "error.type" = field::Empty,
+        );
+        #[cfg(feature = "otel")]
+        otel::annotate(&span, operation, &self.session_table);
+        if tracing::enabled!(Level::DEBUG) {
+            span.record("session_id", field::display(session_id));
+        }
+        let started = Instant::now();
+        let result = future.instrument(span.clone()).await;
+        let elapsed = started.elapsed();
+        span.record("elapsed_ms", elapsed.as_millis() as u64);
+        self.report(|metrics| metrics.operation_completed(operation, elapsed, result.is_ok()));
+        if let Err(e) = &result {
+            span.record("error", field::display(e));
+            #[cfg(feature = "otel")]
+            otel::record_error(&span, e.inner());
+        }
+        self.labeled(operation, result)
+    }
+
+    /// Append the failed `operation` and the session table to a backend
+    /// error, so it can be traced back to the failing query in logs. The
+    /// session id is a credential, so it is not added.
+    fn labeled<T>(&self, operation: &'static str, result: StoreResult<T>) -> StoreResult<T> {
+        result.map_err(|e| {
+            e.map_backend(|e| {
+                format!(
+                    "{e} (operation: {operation}, table: {})",
+                    self.session_table
+                )
+            })
+        })
+    }
+
+    /// Run a single attempt of a store operation, failing it if it takes
+    /// longer than the timeout, if enabled.
+    async fn timed<T>(&self, operation: impl Future<Output = StoreResult<T>>) -> StoreResult<T> {
+        let Some(timeout) = self.timeout else {
+            return operation.await;
+        };
+        tokio::time::timeout(timeout, operation)
+            .await
+            .unwrap_or_else(|_| {
+                Err(SurrealStoreError::timeout(format!(
+                    "{SESSION_TIMEOUT} after {timeout:?}"
+                )))
+            })
+    }
+
+    /// Run a single attempt of a store operation, subject to the circuit
+    /// breaker if enabled.
+    async fn breaker_guarded<T>(
+        &self,
+        operation: impl Future<Output = StoreResult<T>>,
+    ) -> StoreResult<T> {
+        let Some(breaker) = &self.circuit_breaker else {
+            return operation.await;
+        };
+        breaker.acquire()?;
+        let result = operation.await;
+        breaker.record(&result);
+        result
+    }
+
+    async fn delete_expired_records(&self) -> StoreResult<u64> {
+        info!("Deleting expired sessions");
+        let started = Instant::now();
+        let deleted = self
+            .delete_records(&format!(
+                "where {} <= {} and {PINNED_COLUMN} != true",
+                self.expiry_column,
+                self.now()
+            ))
+            .await?;
+        info!(
+            "Deleted {deleted} expired sessions in {:?}",
+            started.elapsed()
+        );
+        Ok(deleted)
+    }
+
+    /// Delete at most `batch_size` expired sessions, returning how many
+    /// were deleted.
+    async fn delete_expired_batch(&self, batch_size: usize) -> StoreResult<u64> {
+        let deleted: Vec<BTreeMap<String, IgnoredAny>> = self
+            .query(format!(
+                "delete (select value id from type::table($table)
+where {column} <= {now} and {PINNED_COLUMN} != true limit $batch_size) return id",
+                column = self.expiry_column,
+                now = self.now()
+            ))
+            .bind(("table", self.session_table.clone()))
+            .bind(("batch_size", batch_size))
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        Ok(deleted.len() as u64)
+    }
+
+    /// Delete the sessions matching the `condition` clause, returning
+    /// how many were deleted.
+    async fn delete_records(&self, condition: &str) -> StoreResult<u64> {
+        let deleted: Vec<BTreeMap<String, IgnoredAny>> = self
+            .query(format!("delete type::table($table) {condition} return id"))
+            .bind(("table", self.session_table.clone()))
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        Ok(deleted.len() as u64)
+    }
+
+    /// Delete the sessions expiring at or before the unix timestamp
+    /// `cutoff`, returning how many were deleted.
+    async fn delete_expired_before_cutoff(&self, cutoff: i64) -> StoreResult<u64> {
+        let deleted: Vec<BTreeMap<String, IgnoredAny>> = self
+            .query(format!(
+                "delete type::table($table) where {} <= {} return id",
+                self.expiry_column,
+                self.expiry_at("$cutoff")
+            ))
+            .bind(("table", self.session_table.clone()))
+            .bind(("cutoff", cutoff))
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        Ok(deleted.len() as u64)
+    }
+
+    /// Create a session, moving it to a new id while its id is taken.
+    /// The create fails if the record exists, so concurrent creates can't
+    /// claim the same id.
+    async fn create_record(&self, session: &mut Record) -> StoreResult<()> {
+        loop {
+            match self.write_session(session, "create", "create", None).await {
+                Err(e) if is_record_exists(&e) => {
+                    session.id = match &self.id_generator {
+                        Some(generator) => (generator.0)(),
+                        None => Id::default(),
+                    };
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Create a session under its own id, returning whether it was
+    /// created or the id is taken.
+    async fn create_record_strict(&self, session: &Record) -> StoreResult<bool> {
+        match self.write_session(session, "create", "create", None).await {
+            Ok(()) => Ok(true),
+            Err(e) if is_record_exists(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Select the stored record of a live session.
+    async fn select_record(&self, session_id: &Id) -> StoreResult<Option<SessionRecord>> {
+        self.select_record_matching(
+            session_id,
+            &format!("where {} > {}", self.expiry_column, self.now()),
+        )
+        .await
+    }
+
+    /// Select the record of a session if it matches the `condition`
+    /// clause.
+    async fn select_record_matching(
+        &self,
+        session_id: &Id,
+        condition: &str,
+    ) -> StoreResult<Option<SessionRecord>> {
+        self.query(format!(
+            "select {} as expiry_date, {} from type::thing($table, $id) {condition}",
+            self.unix_expiry(),
+            self.data_field()
+        ))
+        .bind(("id", self.record_key(session_id)))
+        .bind(("table", self.session_table.clone()))
+        .await
+        .map_err(|e| Error::Backend(e.to_string()))?
+        .take(self.first_statement())
+        .map_err(read_error)
+    }
+
+    async fn load_record(&self, session_id: &Id) -> StoreResult<Option<Record>> {
+        let Some(record) = self.select_record(session_id).await? else {
+            return Ok(None);
+        };
+        let legacy_data = (self.migrate_on_read
+            && !self.encrypted()
+            && self.codec.is_none()
+            && record.is_legacy_encoding()
+            && self.message_pack_encoding == MessagePackEncoding::Named)
+            .then(|| record.data.clone());
+        let stored_data = matches!(
+            self.decode_failure,
+            DecodeFailure::TreatAsMissing { delete: true }
+        )
+        .then(|| record.data.clone());
+        let session = match self.decode(session_id, record).await {
+            Ok(session) => session,
+            Err(e @ SurrealStoreError::Serialization(Error::Decode(_)))
+                if self.decode_failure != DecodeFailure::Error =>
+            {
+                warn!("Treating session that failed to decode as missing: {e}");
+                if let Some(stored_data) = stored_data {
+                    if let Err(e) = self.delete_undecodable(session_id, stored_data).await {
+                        warn!("Failed to delete session that failed to decode: {e}");
+                    }
+                }
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+        if let Some(legacy_data) = legacy_data {
+            if let Err(e) = self.migrate_record(&session, legacy_data).await {
+                warn!("Failed to migrate session to the current encoding: {e}");
+            }
+        }
+        Ok(Some(session))
+    }
+
+    /// Delete a session that failed to decode, if its data is still
+    /// `stored_data`.
+    async fn delete_undecodable(
+        &self,
+        session_id: &Id,
+        stored_data: SessionData,
+    ) -> StoreResult<()> {
+        self.query(format!(
+            "delete type::thing($table, $id) where {} = $stored_data",
+            self.data_column
+        ))
+        .bind(("table", self.session_table.clone()))
+        .bind(("id", self.record_key(session_id)))
+        .bind(("stored_data", stored_data))
+        .await
+        .map_err(write_error)?
+        .check()
+        .map_err(|e| Error::Backend(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Rewrite a session read in an older encoding, if its data is still
+    /// `legacy_data`.
+    async fn migrate_record(&self, session: &Record, legacy_data: SessionData) -> StoreResult<()> {
+        let record = self.encode(session).await?;
+        self.query(format!(
+            "update type::thing($table, $id) set {data} = $data where {data} = $legacy_data",
+            data = self.data_column
+        ))
+        .bind(("table", self.session_table.clone()))
+        .bind(("id", self.record_key(&session.id)))
+        .bind(("data", record.data))
+        .bind(("legacy_data", legacy_data))
+        .await
+        .map_err(write_error)?
+        .check()
+        .map_err(|e| Error::Backend(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Delete a session, returning whether it existed.
+    async fn delete_record(&self, session_id: &Id) -> StoreResult<bool> {
+        let key = self.record_key(session_id);
+        let deleted: Option<BTreeMap<String, IgnoredAny>> = match &self.audit_table {
+            Some(audit_table) => audit_errors(
+                self.audited_query(
+                    "delete type::thing($table, $id) return before",
+                    audit_table,
+                    "delete",
+                    key,
+                )
+                .await
+                .map_err(|e| Error::Backend(e.to_string()))?,
+            )?
+            .take(self.first_statement())
+            .map_err(read_error)?,
+            None => self
+                .query("delete type::thing($table, $id) return before")
+                .bind(("table", self.session_table.clone()))
+                .bind(("id", key))
+                .await
+                .map_err(|e| Error::Backend(e.to_string()))?
+                .take(self.first_statement())
+                .map_err(read_error)?,
+        };
+
+        Ok(deleted.is_some())
+    }
+
+    async fn select_record_ids(
+        &self,
+        ids: Vec<surrealdb::RecordId>,
+    ) -> StoreResult<Vec<KeyedSessionRecord>> {
+        self.query(format!(
+            "select record::id(id) as key, {data}, {unix_expiry} as expiry_date from $ids
+where {column} > {now}",
+            data = self.data_field(),
+            column = self.expiry_column,
+            unix_expiry = self.unix_expiry(),
+            now = self.now()
+        ))
+        .bind(("ids", ids))
+        .await
+        .map_err(|e| Error::Backend(e.to_string()))?
+        .take(self.first_statement())
+        .map_err(read_error)
+    }
+
+    async fn delete_record_ids(&self, ids: Vec<surrealdb::RecordId>) -> StoreResult<()> {
+        let query = match &self.audit_table {
+            Some(audit_table) => self
+                .query(
+                    "begin transaction;
+let $deleted = (delete $ids return before);
+for $session in $deleted {
+    create type::table($audit_table) content {
+        operation: 'delete', session: record::id($session.id), timestamp: time::now()
+    };
+};
+commit transaction;",
+                )
+                .bind(("audit_table", audit_table.clone())),
+            None => self.query("delete $ids"),
+        };
+        let mut response = query
+            .bind(("ids", ids))
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        errors_to_result(response.take_errors(), "Deleting sessions failed")
+    }
+
+    async fn delete_expiry_range(&self, lower: i64, upper: i64) -> StoreResult<u64> {
+        let deleted: Vec<BTreeMap<String, IgnoredAny>> = self
+            .query(format!(
+                "delete type::table($table) where {column} >= {lower} and {column} <= {upper}
+and {PINNED_COLUMN} != true return id",
+                column = self.expiry_column,
+                lower = self.expiry_at("$lower"),
+                upper = self.expiry_at("$upper")
+            ))
+            .bind(("table", self.session_table.clone()))
+            .bind(("lower", lower))
+            .bind(("upper", upper))
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .take(self.first_statement())
+            .map_err(read_error)?;
+        Ok(deleted.len() as u64)
+    }
+}
+
+/// Environment variable holding the SurrealDB connection URL, e.g.
+/// `ws://localhost:8000` or `mem://`. Required.
+#[cfg(feature = "env")]
+pub const ENV_URL: &str = "SURREALDB_URL";
+/// Environment variable holding the namespace to use. Required.
+#[cfg(feature = "env")]
+pub const ENV_NAMESPACE: &str = "SURREALDB_NS";
+/// Environment variable holding the database to use. Required.
+#[cfg(feature = "env")]
+pub const ENV_DATABASE: &str = "SURREALDB_DB";
+/// Environment variable holding the root username to sign in with.
+/// Optional, but must be set together with [`ENV_PASSWORD`].
+#[cfg(feature = "env")]
+pub const ENV_USERNAME: &str = "SURREALDB_USER";
+/// Environment variable holding the root password to sign in with.
+/// Optional, but must be set together with [`ENV_USERNAME`].
+#[cfg(feature = "env")]
+pub const ENV_PASSWORD: &str = "SURREALDB_PASS";
+/// Environment variable holding the session table name. Optional,
+/// defaults to [`DEFAULT_SESSION_TABLE`].
+#[cfg(feature = "env")]
+pub const ENV_SESSION_TABLE: &str = "SURREALDB_SESSION_TABLE";
+
+#[cfg(feature = "env")]
+impl SurrealSessionStore<surrealdb::engine::any::Any> {
+    /// Connect to SurrealDB using the configuration in the environment
+    /// and create a session store on that connection. See [`ENV_URL`],
+    /// [`ENV_NAMESPACE`], [`ENV_DATABASE`], [`ENV_USERNAME`],
+    /// [`ENV_PASSWORD`] and [`ENV_SESSION_TABLE`] for the variables
+    /// read. Returns an error naming the variable if a required one is
+    /// missing.
+    pub async fn from_env() -> StoreResult<Self> {
+        Self::from_vars(|name| std::env::var(name).ok()).await
+    }
+
+    async fn from_vars(var: impl Fn(&str) -> Option<String>) -> StoreResult<Self> {
+        let required = |name: &str| {
+            var(name).ok_or_else(|| {
+                SurrealStoreError::invalid_configuration(format!(
+                    "Missing required environment variable {name}"
+                ))
+            })
+        };
+        let url = required(ENV_URL)?;
+        let namespace = required(ENV_NAMESPACE)?;
+        let database = required(ENV_DATABASE)?;
+        let credentials = match (var(ENV_USERNAME), var(ENV_PASSWORD)) {
+            (Some(username), Some(password)) => Some((username, password)),
+            (None, None) => None,
+            _ => {
+                return Err(SurrealStoreError::invalid_configuration(format!(
+                    "Environment variables {ENV_USERNAME} and {ENV_PASSWORD} must be set together"
+                )))
+            }
+        };
+        let session_table =
+            var(ENV_SESSION_TABLE).unwrap_or_else(|| DEFAULT_SESSION_TABLE.to_string());
+
+        let client = surrealdb::engine::any::connect(url)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        if let Some((username, password)) = credentials {
+            client
+                .signin(surrealdb::opt::auth::Root {
+                    username: &username,
+                    password: &password,
+                })
+                .await
+                .map_err(|e| Error::Backend(e.to_string()))?;
+        }
+        client
+            .use_ns(namespace)
+            .use_db(database)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+
+        Self::try_new(client, session_table)
+    }
+}
+
+/// URL schemes [`SurrealSessionStore::connect_remote`] connects with.
+#[cfg(feature = "remote")]
+const REMOTE_SCHEMES: [&str; 2] = ["ws://", "wss://"];
+
+#[cfg(feature = "remote")]
+impl SurrealSessionStore<surrealdb::engine::any::Any> {
+    /// Connect to the remote SurrealDB server at `url`, sign in with
+    /// `credentials` if given, and create a session store on
+    /// `namespace` and `database`, storing sessions in the
+    /// [`DEFAULT_SESSION_TABLE`] (see [`for_table`](Self::for_table) for
+    /// another one). Available with the `remote` feature.
+    ///
+    /// `url` is a `ws://` URL, or a `wss://` URL to connect over TLS,
+    /// e.g. `wss://db.example.com`; the server's certificate is checked
+    /// with the default TLS configuration of the client. The store
+    /// signs in again with the credentials when they expire, see
+    /// [`with_credentials`](Self::with_credentials), and the session
+    /// table is checked to be readable like in
+    /// [`connect`](Self::connect), so a misconfiguration fails at
+    /// startup.
+    ///
+    /// Fails with [`SurrealStoreError::InvalidConfiguration`] for a URL
+    /// of another scheme, and with [`SurrealStoreError::Connection`]
+    /// naming the step that failed otherwise.
+    pub async fn connect_remote(
+        url: &str,
+        namespace: &str,
+        database: &str,
+        credentials: Option<Credentials>,
+    ) -> StoreResult<Self> {
+        if !REMOTE_SCHEMES.iter().any(|scheme| url.starts_with(scheme)) {
+            return Err(SurrealStoreError::invalid_configuration(format!(
+                "Unsupported SurrealDB URL '{url}': expected a ws:// or wss:// URL"
+            )));
+        }
+        let connection_error = |step: &str, e: surrealdb::Error| {
+            SurrealStoreError::Connection(Error::Backend(format!(
+                "{step} SurrealDB at {url} failed: {e}"
+            )))
+        };
+        let client = surrealdb::engine::any::connect(url)
+            .await
+            .map_err(|e| connection_error("Connecting to", e))?;
+        let store =
+            Self::try_new(client, DEFAULT_SESSION_TABLE.to_string())?.with_credentials(credentials);
+        if let Some(credentials) = &store.credentials {
+            store
+                .sign_in(credentials)
+                .await
+                .map_err(|e| connection_error("Signing in to", e))?;
+        }
+        store
+            .client
+            .use_ns(namespace)
+            .use_db(database)
+            .await
+            .map_err(|e| connection_error("Selecting the namespace and database of", e))?;
+        store.check_readable().await?;
+        Ok(store)
+    }
+}
+
+#[async_trait]
+impl<DB: std::fmt::Debug + surrealdb::Connection> ExpiredDeletion for SurrealSessionStore<DB> {
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.delete_expired_count().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<DB: std::fmt::Debug + surrealdb::Connection> SessionStore for SurrealSessionStore<DB> {
+    async fn create(&self, session: &mut Record) -> session_store::Result<()> {
+        // Each attempt gets its own copy, so a failed attempt can't leave
+        // a changed id behind.
+        let created = self
+            .traced(
+                "create",
+                &session.id,
+                self.guarded(|| {
+                    let mut session = session.clone();
+                    async move {
+                        self.create_record(&mut session).await?;
+                        Ok::<_, SurrealStoreError>(session.id)
+                    }
+                }),
+            )
+            .await?;
+        session.id = created;
+        self.report(|metrics| metrics.session_created());
+        Ok(())
+    }
+
+    async fn save(&self, session: &Record) -> session_store::Result<()> {
+        self.traced(
+            "save",
+            &session.id,
+            self.guarded(|| self.upsert_session(session, "save")),
+        )
+        .await?;
+        self.report(|metrics| metrics.session_saved());
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let session = self
+            .traced(
+                "load",
+                session_id,
+                self.guarded(|| self.load_record(session_id)),
+            )
+            .await?;
+        self.report(|metrics| metrics.session_loaded(session.is_some()));
+        Ok(session)
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.traced("delete", session_id, async {
+            let deleted = self.guarded(|| self.delete_record(session_id)).await?;
+            if !deleted && self.error_on_missing_delete {
+                return Err(SurrealStoreError::not_found("Session to delete not found"));
+            }
+            Ok(())
+        })
+        .await?;
+        self.report(|metrics| metrics.session_deleted());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use tower_sessions::cookie::time::{Duration, OffsetDateTime};
+    use tower_sessions_core::session_store::Result;
+
+    use super::*;
+
+    pub(crate) static SESSIONS_TABLE: &str = "sessions";
+
+    pub(crate) type DB = surrealdb::engine::local::Db;
+
+    pub(crate) async fn new_db_connection() -> Surreal<DB> {
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        db.use_ns("testing")
+            .await
+            .expect("Surreal namespace initialization failure");
+        db.use_db("testing")
+            .await
+            .expect("Surreal database initialization failure");
+        db
+    }
+
+    /// Directory of a file-backed database, removed on drop.
+    #[cfg(feature = "test-rocksdb")]
+    struct TemporaryDirectory(std::path::PathBuf);
+
+    #[cfg(feature = "test-rocksdb")]
+    impl Drop for TemporaryDirectory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[cfg(feature = "test-rocksdb")]
+    async fn open_rocksdb(path: &std::path::Path) -> Surreal<DB> {
+        // A dropped connection releases the database lock in the
+        // background, so opening it again may fail at first.
+        let mut attempts = 1;
+        let db = loop {
+            match Surreal::new::<surrealdb::engine::local::RocksDb>(path).await {
+                Ok(db) => break db,
+                Err(e) if attempts < 50 => {
+                    warn!("Retrying to open RocksDB: {e}");
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    attempts += 1;
+                }
+                Err(e) => panic!("Surreal initialization failure: {e}"),
+            }
+        };
+        db.use_ns("testing")
+            .use_db("testing")
+            .await
+            .expect("Surreal database initialization failure");
+        db
+    }
+
+    #[cfg(feature = "test-rocksdb")]
+    #[tokio::test]
+    async fn rocksdb_persistence() {
+        let directory = TemporaryDirectory(std::env::temp_dir().join(format!(
+            "tower-sessions-surrealdb-store-{}",
+            rand::random::<u64>()
+        )));
+        let live = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let expired = make_record(None, [("key", "value")].to_vec(), Duration::days(-1));
+        let deleted = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        {
+            let store = SurrealSessionStore::new(
+                open_rocksdb(&directory.0).await,
+                SESSIONS_TABLE.to_string(),
+            );
+            let mut created = live.clone();
+            create_session(&store, &mut created).await;
+            assert_eq!(live.id, created.id, "Free id should be kept");
+            for session in [&expired, &deleted] {
+                save_session(&store, session).await;
+            }
+            assert_eq!(Some(live.clone()), load_session(&store, &live).await);
+            assert_eq!(None, load_session(&store, &expired).await);
+            store.delete(&deleted.id).await.expect("Error deleting");
+            assert_eq!(None, load_session(&store, &deleted).await);
+        }
+
+        let store =
+            SurrealSessionStore::new(open_rocksdb(&directory.0).await, SESSIONS_TABLE.to_string());
+        assert_eq!(
+            Some(live.clone()),
+            load_session(&store, &live).await,
+            "Session should survive reopening the database"
+        );
+        assert_eq!(None, load_session(&store, &deleted).await);
+        assert_eq!(
+            1,
+            store
+                .delete_expired_count()
+                .await
+                .expect("Error deleting expired"),
+            "Expired session should survive reopening the database"
+        );
+        assert_eq!(1, store.count_all().await.expect("Error counting"));
+    }
+
+    #[tokio::test]
+    async fn basic_roundtrip() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let record = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        save_session(&store, &record).await;
+        let loaded = load_session(&store, &record).await.expect("Value missing");
+        assert_eq!(record, loaded, "Loaded value should equal original");
+    }
+
+    #[tokio::test]
+    async fn delete_expired() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let expired = make_record(None, [].to_vec(), Duration::ZERO);
+        let expired2 = make_record(None, [("key", "value")].to_vec(), Duration::days(-1));
+        let not_expired = make_record(None, [].to_vec(), Duration::days(1));
+        let not_expired2 = make_record(None, [("key", "value")].to_vec(), Duration::minutes(1));
+
+        for session in [&expired, &expired2, &not_expired, &not_expired2] {
+            save_session(&store, session).await;
+            select_session(&db, session)
+                .await
+                .expect("Session should be in the database");
+        }
+
+        store
+            .delete_expired()
+            .await
+            .expect("Error deleting expired");
+
+        for not_expired in [&not_expired, &not_expired2] {
+            select_session(&db, not_expired)
+                .await
+                .expect("Not-expired session should be in the database");
+
+            let loaded = load_session(&store, not_expired)
+                .await
+                .expect("No session loaded");
+
+            assert_eq!(
+                not_expired, &loaded,
+                "Not-expired session should be loaded from the store",
+            );
+        }
+
+        for expired in [&expired, &expired2] {
+            let loaded = select_session(&db, expired).await;
+            assert!(
+                loaded.is_none(),
+                "Expired session should not be in the database"
+            );
+
+            let loaded = load_session(&store, expired).await;
+
+            assert!(
+                loaded.is_none(),
+                "Expired session should not be loaded from the store",
+            );
+        }
+    }
+
+    #[test]
+    fn decode_ignores_unknown_fields() {
+        #[derive(Serialize)]
+        struct FutureRecord<'a> {
+            id: &'a Id,
+            data: &'a HashMap<String, serde_json::Value>,
+            expiry_date: &'a OffsetDateTime,
+            added_later: &'a str,
+        }
+
+        let record = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let future = FutureRecord {
+            id: &record.id,
+            data: &record.data,
+            expiry_date: &record.expiry_date,
+            added_later: "unknown to this version",
+        };
+        let session_record = SessionRecord {
+            data: SessionData::MessagePack(
+                rmp_serde::to_vec_named(&future).expect("Error encoding"),
+            ),
+            expiry_date: record.expiry_date.unix_timestamp(),
+        };
+        let decoded = session_record
+            .to_session(&record.id, None)
+            .expect("Error decoding");
+        assert_eq!(
+            record, decoded,
+            "Record with an unknown field should decode"
+        );
+    }
+
+    #[test]
+    fn decode_positional_encoding() {
+        let record = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let session_record = SessionRecord {
+            data: SessionData::MessagePack(rmp_serde::to_vec(&record).expect("Error encoding")),
+            expiry_date: record.expiry_date.unix_timestamp(),
+        };
+        let decoded = session_record
+            .to_session(&record.id, None)
+            .expect("Error decoding");
+        assert_eq!(record, decoded, "Positionally encoded record should decode");
+    }
+
+    #[tokio::test]
+    async fn message_pack_encoding() {
+        let db = new_db_connection().await;
+        let named_store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let positional_store = named_store
+            .clone()
+            .with_message_pack_encoding(MessagePackEncoding::Positional);
+        let named = make_record(None, [("key", "named")].to_vec(), Duration::days(1));
+        let positional = make_record(None, [("key", "positional")].to_vec(), Duration::days(1));
+        save_session(&named_store, &named).await;
+        save_session(&positional_store, &positional).await;
+
+        for (session, encoding) in [
+            (&named, MessagePackEncoding::Named),
+            (&positional, MessagePackEncoding::Positional),
+        ] {
+            let record = select_session(&db, session).await.expect("No record");
+            assert_eq!(Some(encoding), record.message_pack_encoding());
+            // Sessions decode the same whichever encoding they were
+            // written in and the store is configured with.
+            for store in [&named_store, &positional_store] {
+                let loaded = load_session(store, session).await.expect("No session");
+                assert_eq!(session, &loaded, "Loaded {encoding:?} session");
+            }
+        }
+
+        let report = positional_store
+            .migrate_records()
+            .await
+            .expect("Error migrating records");
+        assert_eq!((1, 1), (report.migrated, report.skipped));
+        let record = select_session(&db, &named).await.expect("No record");
+        assert!(record.is_legacy_encoding(), "Record should be rewritten");
+        assert_eq!(
+            Some(named.clone()),
+            load_session(&named_store, &named).await
+        );
+    }
+
+    /// Encodes sessions like [`MessagePackCodec`] with the bytes
+    /// reversed, failing for sessions with a `fail` key.
+    struct ReversedCodec;
+
+    impl SessionCodec for ReversedCodec {
+        fn encode(&self, session: &Record) -> Result<Vec<u8>> {
+            if session.data.contains_key("fail") {
+                return Err(Error::Encode("Refusing to encode".to_string()));
+            }
+            let mut data = MessagePackCodec::default().encode(session)?;
+            data.reverse();
+            Ok(data)
+        }
+
+        fn decode(&self, data: &[u8]) -> Result<Record> {
+            let mut data = data.to_vec();
+            data.reverse();
+            MessagePackCodec::default().decode(&data)
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_codec() {
+        let db = new_db_connection().await;
+        let json_store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_serialization_format(SerializationFormat::Json);
+        let store = json_store.clone().with_codec(Some(Arc::new(ReversedCodec)));
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let json = make_record(None, [("key", "json")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        save_session(&json_store, &json).await;
+
+        let record = select_session(&db, &session).await.expect("No record");
+        let SessionData::MessagePack(mut data) = record.data else {
+            panic!("Encoded session should be stored as bytes");
+        };
+        data.reverse();
+        assert_eq!(
+            MessagePackCodec::default()
+                .encode(&session)
+                .expect("Error encoding"),
+            data,
+            "Session should be stored as encoded by the codec"
+        );
+        assert_eq!(Some(session.clone()), load_session(&store, &session).await);
+        assert_eq!(
+            Some(json.clone()),
+            load_session(&store, &json).await,
+            "Sessions of other formats should still load"
+        );
+
+        let report = store
+            .migrate_records()
+            .await
+            .expect("Error migrating records");
+        assert_eq!((1, 1), (report.migrated, report.skipped));
+        let record = select_session(&db, &json).await.expect("No record");
+        assert!(
+            matches!(record.data, SessionData::MessagePack(_)),
+            "Migrated session should be encoded by the codec"
+        );
+        assert_eq!(Some(json.clone()), load_session(&store, &json).await);
+
+        let failing = make_record(None, [("fail", "value")].to_vec(), Duration::days(1));
+        let error = store
+            .save(&failing)
+            .await
+            .expect_err("Codec errors should fail the save");
+        assert!(
+            matches!(error, Error::Encode(_)),
+            "Codec error should be returned: {error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_expired_count() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        for offset in [Duration::ZERO, Duration::days(-1), Duration::days(1)] {
+            save_session(&store, &make_record(None, [].to_vec(), offset)).await;
+        }
+
+        let deleted = store
+            .delete_expired_count()
+            .await
+            .expect("Error deleting expired");
+        assert_eq!(2, deleted, "Deleted expired sessions");
+        let deleted = store
+            .delete_expired_count()
+            .await
+            .expect("Error deleting expired");
+        assert_eq!(0, deleted, "Nothing left to delete");
+        assert_eq!(1, store.count_all().await.expect("Error counting"));
+    }
+
+    #[tokio::test]
+    async fn delete_expired_and_compact() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        for offset in [Duration::days(-1), Duration::days(1)] {
+            save_session(&store, &make_record(None, [].to_vec(), offset)).await;
+        }
+
+        let deleted = store
+            .delete_expired_and_compact()
+            .await
+            .expect("Error deleting expired and compacting");
+        assert_eq!(1, deleted, "Deleted expired sessions");
+        assert_eq!(1, store.count_all().await.expect("Error counting"));
+    }
+
+    #[tokio::test]
+    async fn expired_session_ids() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let sessions: Vec<Record> = [Duration::days(-2), Duration::days(1), Duration::ZERO]
+            .into_iter()
+            .map(|offset| make_record(None, [].to_vec(), offset))
+            .collect();
+        for session in &sessions {
+            save_session(&store, session).await;
+        }
+        let pinned = make_record(None, [].to_vec(), Duration::days(-1));
+        store
+            .save_pinned(&pinned, true)
+            .await
+            .expect("Error saving pinned session");
+
+        let expired: HashSet<Id> = store
+            .expired_session_ids()
+            .await
+            .expect("Error listing expired sessions")
+            .into_iter()
+            .collect();
+        assert_eq!(
+            HashSet::from([sessions[0].id, sessions[2].id]),
+            expired,
+            "Only expired sessions should be listed"
+        );
+        assert_eq!(4, store.count_all().await.expect("Error counting"));
+        assert_eq!(
+            2,
+            store
+                .delete_expired_count()
+                .await
+                .expect("Error deleting expired"),
+            "The listed sessions are those a cleanup deletes"
+        );
+    }
+
+    #[tokio::test]
+    async fn save_many() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_audit_table(Some("session_audit".to_string()));
+        let sessions: Vec<Record> = (0..3)
+            .map(|i| {
+                make_record(
+                    None,
+                    [("key", i.to_string().as_str())].to_vec(),
+                    Duration::days(1),
+                )
+            })
+            .collect();
+
+        store
+            .save_many(&sessions)
+            .await
+            .expect("Error saving sessions");
+        for session in &sessions {
+            assert_eq!(Some(session.clone()), load_session(&store, session).await);
+        }
+        let audited: Vec<IgnoredAny> = db
+            .select("session_audit")
+            .await
+            .expect("Error selecting audit rows");
+        assert_eq!(3, audited.len(), "Each saved session should be audited");
+        store
+            .save_many(&[])
+            .await
+            .expect("Saving nothing should succeed");
+    }
+
+    #[tokio::test]
+    async fn save_many_atomic() {
+        let db = new_db_connection().await;
+        db.query(format!(
+            "define field expiry_date on table {SESSIONS_TABLE} type int assert $value > 0"
+        ))
+        .await
+        .expect("Error defining field")
+        .check()
+        .expect("Error defining field");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        // The expiry date of the middle session is before 1970, failing
+        // the assertion.
+        let sessions = [Duration::days(1), Duration::weeks(-3000), Duration::days(2)]
+            .map(|offset| make_record(None, [].to_vec(), offset));
+
+        store
+            .save_many(&sessions)
+            .await
+            .expect_err("Saving an invalid session should fail");
+        assert_eq!(
+            0,
+            store.count_all().await.expect("Error counting"),
+            "No session should be written"
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_expired_before() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let old = make_record(None, [].to_vec(), Duration::days(-30));
+        let at_cutoff = make_record(None, [].to_vec(), Duration::days(-10));
+        let recent = make_record(None, [].to_vec(), Duration::days(-1));
+        let live = make_record(None, [].to_vec(), Duration::days(1));
+        for session in [&old, &at_cutoff, &recent, &live] {
+            save_session(&store, session).await;
+        }
+
+        let deleted = store
+            .delete_expired_before(at_cutoff.expiry_date)
+            .await
+            .expect("Error deleting sessions");
+        assert_eq!(2, deleted, "Deleted count");
+        for session in [&old, &at_cutoff] {
+            assert!(
+                select_session(&db, session).await.is_none(),
+                "Session expired before the cutoff should be deleted"
+            );
+        }
+        for session in [&recent, &live] {
+            assert!(
+                select_session(&db, session).await.is_some(),
+                "Session expired after the cutoff should remain"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn delete_all() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        for offset in [Duration::days(-1), Duration::days(1), Duration::weeks(2)] {
+            save_session(&store, &make_record(None, [].to_vec(), offset)).await;
+        }
+
+        let deleted = store.delete_all().await.expect("Error deleting all");
+        assert_eq!(3, deleted, "Deleted all sessions");
+        assert_eq!(0, store.count_all().await.expect("Error counting"));
+        let deleted = store.delete_all().await.expect("Error deleting all");
+        assert_eq!(0, deleted, "Nothing left to delete");
+    }
+
+    #[tokio::test]
+    async fn list_session_ids_for_user() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_user_id_key(Some("user_id".to_string()));
+        store.ensure_table().await.expect("Error defining table");
+        let alice = make_record(None, [("user_id", "alice")].to_vec(), Duration::days(1));
+        let alice2 = make_record(None, [("user_id", "alice")].to_vec(), Duration::hours(1));
+        let alice_expired = make_record(None, [("user_id", "alice")].to_vec(), Duration::days(-1));
+        let bob = make_record(None, [("user_id", "bob")].to_vec(), Duration::days(1));
+        let mut numeric = make_record(None, [].to_vec(), Duration::days(1));
+        numeric
+            .data
+            .insert("user_id".to_string(), serde_json::json!(42));
+        let anonymous = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        for session in [&alice, &alice2, &alice_expired, &bob, &numeric, &anonymous] {
+            save_session(&store, session).await;
+        }
+
+        let list = |user_id: &'static str| {
+            let store = store.clone();
+            async move {
+                let mut ids = store
+                    .list_session_ids_for_user(user_id)
+                    .await
+                    .expect("Error listing sessions");
+                ids.sort_by_key(|id| id.0);
+                ids
+            }
+        };
+        let mut expected = vec![alice.id, alice2.id];
+        expected.sort_by_key(|id| id.0);
+        assert_eq!(expected, list("alice").await, "Live sessions of alice");
+        assert_eq!(vec![bob.id], list("bob").await);
+        assert_eq!(vec![numeric.id], list("42").await);
+        assert!(list("carol").await.is_empty());
+
+        // Replacing the data moves the session to another user.
+        store
+            .replace_data(&bob.id, alice.data.clone())
+            .await
+            .expect("Error replacing data");
+        assert!(list("bob").await.is_empty());
+        assert_eq!(3, list("alice").await.len());
+
+        let without_key = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        assert!(without_key
+            .list_session_ids_for_user("alice")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn delete_by_user() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_user_id_key(Some("user_id".to_string()));
+        let other_app = store.clone().with_key_prefix(Some("app".to_string()));
+        let alice = make_record(None, [("user_id", "alice")].to_vec(), Duration::days(1));
+        let alice_expired = make_record(None, [("user_id", "alice")].to_vec(), Duration::days(-1));
+        let bob = make_record(None, [("user_id", "bob")].to_vec(), Duration::days(1));
+        let alice_other_app = make_record(None, [("user_id", "alice")].to_vec(), Duration::days(1));
+        for session in [&alice, &alice_expired, &bob] {
+            save_session(&store, session).await;
+        }
+        save_session(&other_app, &alice_other_app).await;
+
+        let deleted = store
+            .delete_by_user("alice")
+            .await
+            .expect("Error deleting sessions");
+        assert_eq!(2, deleted, "Deleted sessions of alice, including expired");
+        assert_eq!(None, load_session(&store, &alice).await);
+        assert_eq!(Some(bob.clone()), load_session(&store, &bob).await);
+        assert_eq!(
+            Some(alice_other_app.clone()),
+            load_session(&other_app, &alice_other_app).await,
+            "Sessions of other key prefixes should be kept"
+        );
+        let deleted = store
+            .delete_by_user("alice")
+            .await
+            .expect("Error deleting sessions");
+        assert_eq!(0, deleted, "Nothing left to delete");
+    }
+
+    #[tokio::test]
+    async fn delete_by_user_audited() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_user_id_key(Some("user_id".to_string()))
+            .with_audit_table(Some("session_audit".to_string()));
+        let sessions: Vec<Record> = (0..2)
+            .map(|_| make_record(None, [("user_id", "alice")].to_vec(), Duration::days(1)))
+            .collect();
+        for session in &sessions {
+            save_session(&store, session).await;
+        }
+
+        let deleted = store
+            .delete_by_user("alice")
+            .await
+            .expect("Error deleting sessions");
+        assert_eq!(2, deleted, "Deleted sessions of alice");
+        let audited: HashSet<String> = db
+            .query("select value session from session_audit where operation = 'delete'")
+            .await
+            .expect("Error selecting audit rows")
+            .take::<Vec<String>>(0)
+            .expect("Error taking audit rows")
+            .into_iter()
+            .collect();
+        assert_eq!(
+            sessions
+                .iter()
+                .map(|session| session.id.to_string())
+                .collect::<HashSet<_>>(),
+            audited,
+            "Deleted sessions should be audited"
+        );
+    }
+
+    #[tokio::test]
+    async fn load_many() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_key_prefix(Some("app".to_string()));
+        let live = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let live2 = make_record(None, [].to_vec(), Duration::minutes(1));
+        let expired = make_record(None, [].to_vec(), Duration::days(-1));
+        for session in [&live, &live2, &expired] {
+            save_session(&store, session).await;
+        }
+
+        let loaded = store
+            .load_many(&[live.id, expired.id, Id::default(), live2.id])
+            .await
+            .expect("Error loading sessions");
+        assert_eq!(
+            HashMap::from([(live.id, live), (live2.id, live2)]),
+            loaded,
+            "Only live sessions should be loaded"
+        );
+        assert!(store
+            .load_many(&[])
+            .await
+            .expect("Error loading nothing")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn scoped() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let store_a = store
+            .clone()
+            .with_scope("testing".to_string(), "a".to_string())
+            .expect("Error scoping store");
+        let store_b = store
+            .clone()
+            .with_scope("testing".to_string(), "b".to_string())
+            .expect("Error scoping store");
+        let session_a = make_record(None, [("db", "a")].to_vec(), Duration::days(1));
+        let session_b = Record {
+            data: make_record(None, [("db", "b")].to_vec(), Duration::days(1)).data,
+            ..session_a.clone()
+        };
+        let only_a = make_record(None, [].to_vec(), Duration::days(1));
+
+        tokio::join!(
+            save_session(&store_a, &session_a),
+            save_session(&store_b, &session_b),
+            save_session(&store_a, &only_a),
+        );
+        let (loaded_a, loaded_b) = tokio::join!(
+            load_session(&store_a, &session_a),
+            load_session(&store_b, &session_b)
+        );
+        assert_eq!(Some(session_a.clone()), loaded_a);
+        assert_eq!(Some(session_b), loaded_b);
+        assert_eq!(None, load_session(&store_b, &only_a).await);
+        assert_eq!(
+            None,
+            load_session(&store, &only_a).await,
+            "The database of the client should be unaffected"
+        );
+
+        store_b
+            .delete(&session_a.id)
+            .await
+            .expect("Error deleting session");
+        assert_eq!(
+            Some(session_a.clone()),
+            load_session(&store_a, &session_a).await
+        );
+        assert_eq!(2, store_a.count_all().await.expect("Error counting"));
+        assert_eq!(0, store_b.count_all().await.expect("Error counting"));
+
+        assert!(store
+            .with_scope("testing".to_string(), "a;".to_string())
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn tracing_spans() {
+        /// Subscriber recording the fields of every span.
+        #[derive(Default, Clone)]
+        struct SpanRecorder(Arc<Mutex<Vec<BTreeMap<String, String>>>>);
+
+        struct FieldRecorder<'a>(&'a mut BTreeMap<String, String>);
+
+        impl tracing::field::Visit for FieldRecorder<'_> {
+            fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+                self.0.insert(field.name().to_string(), value.to_string());
+            }
+
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                self.0
+                    .insert(field.name().to_string(), format!("{value:?}"));
+            }
+        }
+
+        impl tracing::Subscriber for SpanRecorder {
+            fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+                let mut fields =
+                    BTreeMap::from([("name".to_string(), span.metadata().name().to_string())]);
+                span.record(&mut FieldRecorder(&mut fields));
+                let mut spans = self.0.lock().expect("Poisoned");
+                spans.push(fields);
+                tracing::span::Id::from_u64(spans.len() as u64)
+            }
+
+            fn record(&self, span: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+                let mut spans = self.0.lock().expect("Poisoned");
+                values.record(&mut FieldRecorder(&mut spans[span.into_u64() as usize - 1]));
+            }
+
+            fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+            fn event(&self, _: &tracing::Event<'_>) {}
+
+            fn enter(&self, _: &tracing::span::Id) {}
+
+            fn exit(&self, _: &tracing::span::Id) {}
+        }
+
+        let recorder = SpanRecorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "secret value")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        load_session(&store, &session).await.expect("No session");
+        store.delete(&session.id).await.expect("Error deleting");
+        let unscoped = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        SurrealSessionStore::new(unscoped, SESSIONS_TABLE.to_string())
+            .load(&session.id)
+            .await
+            .expect_err("Load should fail");
+
+        let spans = recorder.0.lock().expect("Poisoned").clone();
+        let store_spans: Vec<&BTreeMap<String, String>> = spans
+            .iter()
+            .filter(|span| span["name"] == "session_store")
+            .collect();
+        let operations: Vec<&str> = store_spans
+            .iter()
+            .map(|span| span["operation"].as_str())
+            .collect();
+        assert_eq!(vec!["save", "load", "delete", "load"], operations);
+        for span in &store_spans {
+            assert_eq!(SESSIONS_TABLE, span["table"], "Table: {span:?}");
+            assert_eq!(session.id.to_string(), span["session_id"], "Id: {span:?}");
+            assert!(span.contains_key("elapsed_ms"), "Elapsed time: {span:?}");
+        }
+        assert_eq!(
+            vec![false, false, false, true],
+            store_spans
+                .iter()
+                .map(|span| span.contains_key("error"))
+                .collect::<Vec<_>>(),
+            "Errors should be recorded"
+        );
+        assert!(
+            !store_spans
+                .iter()
+                .flat_map(|span| span.values())
+                .any(|value| value.contains("secret value")),
+            "Session data should never be recorded"
+        );
+    }
+
+    #[cfg(feature = "otel")]
+    #[tokio::test]
+    async fn otel_span_attributes() {
+        use opentelemetry::trace::{SpanKind, Status, TracerProvider as _};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let exporter =
+            opentelemetry_sdk::testing::trace::InMemorySpanExporterBuilder::new().build();
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        load_session(&store, &session).await.expect("No session");
+
+        let spans = exporter.get_finished_spans().expect("Error getting spans");
+        let span = spans
+            .iter()
+            .find(|span| span.name == format!("load {SESSIONS_TABLE}"))
+            .expect("No load span");
+        let attributes: BTreeMap<&str, String> = span
+            .attributes
+            .iter()
+            .map(|attribute| (attribute.key.as_str(), attribute.value.to_string()))
+            .collect();
+        for (key, value) in [
+            ("db.system", "surrealdb"),
+            ("db.operation.name", "load"),
+            ("db.collection.name", SESSIONS_TABLE),
+        ] {
+            assert_eq!(
+                Some(value),
+                attributes.get(key).map(String::as_str),
+                "Attribute {key}: {attributes:?}"
+            );
+        }
+        assert!(!attributes.contains_key("error.type"), "{attributes:?}");
+        assert_eq!(SpanKind::Client, span.span_kind);
+        assert_eq!(Status::Unset, span.status);
+    }
+
+    #[derive(Default)]
+    struct RecordingMetrics {
+        counters: Mutex<BTreeMap<String, u64>>,
+    }
+
+    impl RecordingMetrics {
+        fn add(&self, name: impl Into<String>, count: u64) {
+            *self
+                .counters
+                .lock()
+                .unwrap()
+                .entry(name.into())
+                .or_default() += count;
+        }
+    }
+
+    impl SessionMetrics for RecordingMetrics {
+        fn session_created(&self) {
+            self.add("created", 1);
+        }
+
+        fn session_saved(&self) {
+            self.add("saved", 1);
+        }
+
+        fn session_loaded(&self, hit: bool) {
+            self.add(if hit { "load_hit" } else { "load_miss" }, 1);
+        }
+
+        fn session_deleted(&self) {
+            self.add("deleted", 1);
+        }
+
+        fn expired_deleted(&self, count: u64) {
+            self.add("expired_deleted", count);
+        }
+
+        fn operation_completed(
+            &self,
+            operation: &'static str,
+            _elapsed: std::time::Duration,
+            success: bool,
+        ) {
+            assert!(success, "Operation {operation} failed");
+            self.add(format!("{operation}_latency"), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn metrics() {
+        let db = new_db_connection().await;
+        let metrics = Arc::new(RecordingMetrics::default());
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_metrics(Some(metrics.clone()));
+        let mut session = make_record(None, [("key", "value")].to_vec(), Duration::hours(1));
+        create_session(&store, &mut session).await;
+        save_session(&store, &session).await;
+        load_session(&store, &session)
+            .await
+            .expect("Session missing");
+        store.delete(&session.id).await.expect("Deleting failed");
+        assert_eq!(None, load_session(&store, &session).await);
+        save_session(&store, &make_record(None, [].to_vec(), Duration::hours(-1))).await;
+        store
+            .delete_expired()
+            .await
+            .expect("Deleting expired failed");
+
+        let expected = [
+            ("created", 1),
+            ("create_latency", 1),
+            ("saved", 2),
+            ("save_latency", 2),
+            ("load_hit", 1),
+            ("load_miss", 1),
+            ("load_latency", 2),
+            ("deleted", 1),
+            ("delete_latency", 1),
+            ("expired_deleted", 1),
+            ("delete_expired_latency", 1),
+        ]
+        .into_iter()
+        .map(|(name, count)| (name.to_string(), count))
+        .collect::<BTreeMap<_, _>>();
+        assert_eq!(expected, *metrics.counters.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn expiry_event() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        store
+            .define_expiry_event()
+            .await
+            .expect("Error defining event");
+        store
+            .define_expiry_event()
+            .await
+            .expect("Defining the event is idempotent");
+        let expired = make_record(None, [].to_vec(), Duration::days(-1));
+        let expired2 = make_record(None, [("key", "value")].to_vec(), Duration::ZERO);
+        save_session(&store, &expired).await;
+        save_session(&store, &expired2).await;
+
+        let live = make_record(None, [].to_vec(), Duration::days(1));
+        save_session(&store, &live).await;
+        assert!(
+            select_session(&db, &expired).await.is_none(),
+            "Expired session should be purged without delete_expired"
+        );
+        assert!(
+            select_session(&db, &expired2).await.is_none(),
+            "Expired session should be purged without delete_expired"
+        );
+        assert_eq!(
+            Some(live.clone()),
+            load_session(&store, &live).await,
+            "Live session should remain"
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_expired_with_callback() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        for offset in [Duration::days(-1), Duration::days(-2), Duration::days(1)] {
+            save_session(&store, &make_record(None, [].to_vec(), offset)).await;
+        }
+
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let cleanup = tokio::spawn(store.clone().continuously_delete_expired_with_callback(
+            std::time::Duration::from_millis(10),
+            {
+                let deleted = Arc::clone(&deleted);
+                move |result| {
+                    let count = result.expect("Error deleting expired");
+                    deleted.lock().unwrap().push(count);
+                }
+            },
+        ));
+        while deleted.lock().unwrap().len() < 2 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        cleanup.abort();
+        assert_eq!(
+            [2, 0],
+            deleted.lock().unwrap()[..2],
+            "Deleted sessions per cleanup"
+        );
+        assert_eq!(1, store.count_all().await.expect("Error counting"));
+    }
+
+    #[test]
+    fn cleanup_jitter() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let period = std::time::Duration::from_secs(60);
+        let mut rng = StdRng::seed_from_u64(7);
+        let delays: Vec<_> = (0..1000)
+            .map(|_| jittered(period, DEFAULT_CLEANUP_JITTER, rng.gen()))
+            .collect();
+        let (min, max) = (period.mul_f64(0.9), period.mul_f64(1.1));
+        assert!(
+            delays.iter().all(|delay| (min..=max).contains(delay)),
+            "Delays should be within 10% of the period"
+        );
+        assert!(
+            delays.iter().any(|delay| *delay < period)
+                && delays.iter().any(|delay| *delay > period),
+            "Delays should vary either way"
+        );
+
+        assert_eq!(min, jittered(period, DEFAULT_CLEANUP_JITTER, 0.0));
+        assert_eq!(max, jittered(period, DEFAULT_CLEANUP_JITTER, 1.0));
+        assert_eq!(period, jittered(period, 0.0, 0.3), "No jitter");
+        assert_eq!(period, jittered(period, f64::NAN, 0.3), "NaN jitter");
+        assert_eq!(
+            std::time::Duration::ZERO,
+            jittered(period, 5.0, 0.0),
+            "Jitter is clamped to the period"
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_expired_with_shutdown() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let expired = make_record(None, [].to_vec(), Duration::days(-1));
+        save_session(&store, &expired).await;
+
+        let shutdown = tokio::time::sleep(std::time::Duration::from_millis(10));
+        tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            store.clone().continuously_delete_expired_with_shutdown(
+                std::time::Duration::from_secs(3600),
+                shutdown,
+            ),
+        )
+        .await
+        .expect("Cleanup should stop on shutdown")
+        .expect("Error deleting expired");
+        assert!(
+            select_session(&db, &expired).await.is_none(),
+            "Expired session should be deleted before returning"
+        );
+    }
+
+    #[tokio::test]
+    async fn spawn_cleanup() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let period = std::time::Duration::from_millis(5);
+        let expired = make_record(None, [].to_vec(), Duration::days(-1));
+        save_session(&store, &expired).await;
+
+        let handle = store.clone().spawn_cleanup(period);
+        tokio::time::sleep(period * 4).await;
+        assert!(
+            select_session(&db, &expired).await.is_none(),
+            "The cleanup task should delete expired sessions"
+        );
+        handle.abort();
+        let error = handle.await.expect_err("Aborted task should not complete");
+        assert!(error.is_cancelled(), "Task should be cancelled: {error}");
+        save_session(&store, &expired).await;
+        tokio::time::sleep(period * 4).await;
+        assert!(
+            select_session(&db, &expired).await.is_some(),
+            "Aborting should stop the cleanup loop"
+        );
+
+        let mut tasks = tokio::task::JoinSet::new();
+        let cleanup = store.clone().spawn_cleanup_in(period, &mut tasks);
+        tokio::time::sleep(period * 4).await;
+        assert!(
+            select_session(&db, &expired).await.is_none(),
+            "The cleanup task in the set should delete expired sessions"
+        );
+        cleanup.abort();
+        let error = tasks
+            .join_next()
+            .await
+            .expect("Task should be in the set")
+            .expect_err("Aborted task should not complete");
+        assert!(error.is_cancelled(), "Task should be cancelled: {error}");
+    }
+
+    #[tokio::test]
+    async fn delete_many() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_error_on_missing_delete(true);
+        let sessions: Vec<Record> = (0..4)
+            .map(|_| make_record(None, [].to_vec(), Duration::days(1)))
+            .collect();
+        for session in &sessions {
+            save_session(&store, session).await;
+        }
+
+        store
+            .delete_many(&[sessions[0].id, sessions[2].id, Id::default()])
+            .await
+            .expect("Error deleting sessions");
+        for (i, session) in sessions.iter().enumerate() {
+            assert_eq!(
+                i % 2 == 1,
+                select_session(&db, session).await.is_some(),
+                "Only the given sessions should be deleted: {i}"
+            );
+        }
+        store
+            .delete_many(&[])
+            .await
+            .expect("Error deleting nothing");
+    }
+
+    #[tokio::test]
+    async fn delete_expired_parallel() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let expired: Vec<Record> = (1..=10)
+            .map(|days| make_record(None, [].to_vec(), Duration::days(-days)))
+            .collect();
+        let not_expired = make_record(None, [].to_vec(), Duration::days(1));
+
+        for session in expired.iter().chain([&not_expired]) {
+            save_session(&store, session).await;
+        }
+
+        let deleted = store
+            .delete_expired_parallel(4)
+            .await
+            .expect("Error deleting expired");
+        assert_eq!(10, deleted, "Deleted expired sessions");
+
+        for session in &expired {
+            assert!(
+                select_session(&db, session).await.is_none(),
+                "Expired session should not be in the database"
+            );
+        }
+        select_session(&db, &not_expired)
+            .await
+            .expect("Not-expired session should be in the database");
+    }
+
+    #[tokio::test]
+    async fn delete_expired_batched() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let expired: Vec<Record> = (1..=25)
+            .map(|minutes| make_record(None, [].to_vec(), Duration::minutes(-minutes)))
+            .collect();
+        let not_expired = make_record(None, [].to_vec(), Duration::days(1));
+        for session in expired.iter().chain([&not_expired]) {
+            save_session(&store, session).await;
+        }
+
+        store
+            .delete_expired_batched(0)
+            .await
+            .expect_err("Batch size 0 should be rejected");
+        let deleted = store
+            .delete_expired_batched(4)
+            .await
+            .expect("Error deleting expired");
+        assert_eq!(25, deleted, "All expired sessions should be deleted");
+        assert_eq!(1, store.count_all().await.expect("Error counting"));
+        select_session(&db, &not_expired)
+            .await
+            .expect("Not-expired session should be in the database");
+
+        let deleted = store
+            .delete_expired_batched(5)
+            .await
+            .expect("Error deleting expired");
+        assert_eq!(0, deleted, "Nothing left to delete");
+    }
+
+    #[tokio::test]
+    async fn keep_alive_stops_with_store() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_keep_alive(Some(std::time::Duration::from_millis(5)));
+        let clone = store.clone();
+        let task = store
+            .keep_alive
+            .as_ref()
+            .expect("Keep-alive should be running")
+            .0
+            .abort_handle();
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        let session = make_record(None, [].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+
+        drop(store);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(
+            !task.is_finished(),
+            "Keep-alive should run while a clone exists"
+        );
+
+        drop(clone);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(task.is_finished(), "Keep-alive should stop with the store");
+    }
+
+    #[tokio::test]
+    async fn rekey_prefix() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let sessions: Vec<Record> = (0..3)
+            .map(|_| make_record(None, [("key", "value")].to_vec(), Duration::days(1)))
+            .collect();
+        let other = make_record(None, [].to_vec(), Duration::days(1));
+
+        for session in &sessions {
+            upsert_raw(&db, &format!("old_{}", session.id), session).await;
+        }
+        upsert_raw(&db, &format!("other_{}", other.id), &other).await;
+
+        let moved = store
+            .rekey_prefix("old_", "new_")
+            .await
+            .expect("Error rekeying sessions");
+        assert_eq!(3, moved, "Number of sessions moved");
+
+        for session in &sessions {
+            let old: Option<SessionRecord> = db
+                .select((SESSIONS_TABLE, format!("old_{}", session.id)))
+                .await
+                .expect("Error selecting session");
+            assert!(old.is_none(), "Old key should be gone");
+            let new: Option<SessionRecord> = db
+                .select((SESSIONS_TABLE, format!("new_{}", session.id)))
+                .await
+                .expect("Error selecting session");
+            assert_eq!(
+                Some(make_session_record(session).await),
+                new,
+                "Session should be under the new key"
+            );
+        }
+        let other_record: Option<SessionRecord> = db
+            .select((SESSIONS_TABLE, format!("other_{}", other.id)))
+            .await
+            .expect("Error selecting session");
+        assert!(
+            other_record.is_some(),
+            "Non-matching key should be untouched"
+        );
+
+        let rejected = store.rekey_prefix("new_", "new_v2_").await;
+        assert!(rejected.is_err(), "Extending prefix should be rejected");
+    }
+
+    #[tokio::test]
+    async fn rekey_prefix_keeps_user_id() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_user_id_key(Some("user_id".to_string()));
+        let old = store.clone().with_key_prefix(Some("old".to_string()));
+        let new = store.with_key_prefix(Some("new".to_string()));
+        let session = make_record(None, [("user_id", "alice")].to_vec(), Duration::days(1));
+        save_session(&old, &session).await;
+
+        let moved = new
+            .rekey_prefix("old_", "new_")
+            .await
+            .expect("Error rekeying sessions");
+        assert_eq!(1, moved, "Number of sessions moved");
+        assert_eq!(
+            1,
+            new.delete_by_user("alice")
+                .await
+                .expect("Error deleting by user"),
+            "Moved session should keep its user id"
+        );
+        assert_eq!(None, load_session(&new, &session).await);
+    }
+
+    #[tokio::test]
+    async fn save_with_extra_returned_fields() {
+        let db = new_db_connection().await;
+        db.query(format!(
+            "define field saved_at on {SESSIONS_TABLE} value time::now()"
+        ))
+        .await
+        .expect("Error defining field");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(session, loaded, "Loaded session");
+    }
+
+    #[tokio::test]
+    async fn save_with_missing_returned_fields() {
+        let db = new_db_connection().await;
+        db.query(format!(
+            "define table {SESSIONS_TABLE} schemafull;
+define field data on {SESSIONS_TABLE} type array<int>;"
+        ))
+        .await
+        .expect("Error defining table");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [].to_vec(), Duration::days(1));
+        let error = store
+            .save(&session)
+            .await
+            .expect_err("Save should report the missing field");
+        let message = error.to_string();
+        assert!(
+            message.contains("missing [\"expiry_date\"]"),
+            "Error should name the missing field: {message}"
+        );
+    }
+
+    #[tokio::test]
+    async fn blocking_roundtrip() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_blocking_threshold(Some(16));
+        let large_value = "value".repeat(100);
+        let large = make_record(
+            None,
+            [("key", large_value.as_str())].to_vec(),
+            Duration::days(1),
+        );
+        let small = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+
+        for session in [&large, &small] {
+            save_session(&store, session).await;
+            let loaded = load_session(&store, session).await.expect("No session");
+            assert_eq!(session, &loaded, "Loaded session");
+        }
+    }
+
+    #[tokio::test]
+    async fn record_key_format() {
+        let db = new_db_connection().await;
+        let session = make_record(None, [].to_vec(), Duration::days(1));
+        let stores = [
+            (
+                SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string()),
+                session.id.to_string(),
+            ),
+            (
+                SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+                    .with_key_prefix(Some("app".to_string())),
+                format!("app_{}", session.id),
+            ),
+            (
+                SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+                    .with_key_prefix(Some("app".to_string()))
+                    .with_key_separator(String::new()),
+                format!("app{}", session.id),
+            ),
+        ];
+
+        for (store, expected_key) in stores {
+            save_session(&store, &session).await;
+            let keys: Vec<String> = db
+                .query("select value record::id(id) from type::table($table)")
+                .bind(("table", SESSIONS_TABLE))
+                .await
+                .expect("Error selecting keys")
+                .take(0)
+                .expect("Error taking keys");
+            assert_eq!(vec![expected_key], keys, "Record key on disk");
+
+            let loaded = load_session(&store, &session).await;
+            assert_eq!(Some(&session), loaded.as_ref(), "Loaded session");
+            store
+                .delete(&session.id)
+                .await
+                .expect("Error deleting session");
+        }
+    }
+
+    #[tokio::test]
+    async fn record_key_special_characters() {
+        let db = new_db_connection().await;
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let prefix = "x:y⟨z⟩`'\"; delete sessions; --";
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_key_prefix(Some(prefix.to_string()))
+            .with_key_separator(":".to_string());
+        save_session(&store, &session).await;
+
+        let keys: Vec<String> = db
+            .query("select value record::id(id) from type::table($table)")
+            .bind(("table", SESSIONS_TABLE))
+            .await
+            .expect("Error selecting keys")
+            .take(0)
+            .expect("Error taking keys");
+        assert_eq!(
+            vec![format!("{prefix}:{}", session.id)],
+            keys,
+            "Record key should be stored literally"
+        );
+        assert_eq!(Some(session.clone()), load_session(&store, &session).await);
+        assert!(store.exists(&session.id).await.expect("Error checking"));
+        let exported: Vec<(Id, Record)> = store
+            .export_stream()
+            .try_collect()
+            .await
+            .expect("Error exporting sessions");
+        assert_eq!(vec![(session.id, session.clone())], exported);
+
+        let other = make_record(None, [].to_vec(), Duration::days(1));
+        let unprefixed = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        save_session(&unprefixed, &other).await;
+        store.delete(&session.id).await.expect("Error deleting");
+        assert_eq!(None, load_session(&store, &session).await);
+        assert_eq!(
+            Some(other.clone()),
+            load_session(&unprefixed, &other).await,
+            "Other sessions should be untouched"
+        );
+    }
+
+    #[tokio::test]
+    async fn list_session_tables() {
+        let db = new_db_connection().await;
+        for table in [
+            SESSIONS_TABLE,
+            "sessions_acme",
+            "sessions_globex",
+            "sessionsx",
+        ] {
+            let store = SurrealSessionStore::new(db.clone(), table.to_string());
+            save_session(&store, &make_record(None, [].to_vec(), Duration::days(1))).await;
+        }
+        db.query("create users:1")
+            .await
+            .expect("Error creating user");
+
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let tables = store
+            .list_session_tables()
+            .await
+            .expect("Error listing tables");
+        assert_eq!(
+            vec!["sessions", "sessions_acme", "sessions_globex"],
+            tables,
+            "Session tables"
+        );
+    }
+
+    #[tokio::test]
+    async fn export_stream() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let mut sessions: HashMap<Id, Record> = HashMap::new();
+        for i in 0..EXPORT_PAGE_SIZE + 5 {
+            let offset = if i % 2 == 0 { 1 } else { -1 };
+            let session = make_record(None, [("key", "value")].to_vec(), Duration::days(offset));
+            save_session(&store, &session).await;
+            sessions.insert(session.id, session);
+        }
+
+        let exported: HashMap<Id, Record> = store
+            .export_stream()
+            .try_collect()
+            .await
+            .expect("Error exporting sessions");
+        assert_eq!(sessions, exported, "Exported sessions");
+    }
+
+    #[tokio::test]
+    async fn stream_all() {
+        use futures_util::StreamExt;
+
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let mut sessions: HashMap<Id, Record> = HashMap::new();
+        for i in 0..EXPORT_PAGE_SIZE * 2 + 1 {
+            let offset = if i % 2 == 0 { 1 } else { -1 };
+            let session = make_record(None, [("key", "value")].to_vec(), Duration::days(offset));
+            save_session(&store, &session).await;
+            sessions.insert(session.id, session);
+        }
+        let invalid = make_record(None, [].to_vec(), Duration::days(1));
+        let _: Option<SessionRecord> = db
+            .upsert((SESSIONS_TABLE, invalid.id.to_string()))
+            .content(SessionRecord {
+                data: SessionData::MessagePack(vec![0x92, 0xc1]),
+                expiry_date: invalid.expiry_date.unix_timestamp(),
+            })
+            .await
+            .expect("Error writing record");
+
+        let items: Vec<StoreResult<(Id, Record)>> = store
+            .stream_all()
+            .await
+            .expect("Error streaming sessions")
+            .collect()
+            .await;
+        let (streamed, failed): (Vec<_>, Vec<_>) = items.into_iter().partition(StoreResult::is_ok);
+        assert_eq!(1, failed.len(), "Undecodable session should be an error");
+        let streamed: HashMap<Id, Record> = streamed.into_iter().map(StoreResult::unwrap).collect();
+        assert_eq!(sessions, streamed, "Streamed sessions");
+    }
+
+    #[tokio::test]
+    async fn page_size() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        assert!(
+            store.clone().with_page_size(0).is_err(),
+            "Page size 0 should be rejected"
+        );
+        let store = store.with_page_size(2).expect("Error setting page size");
+        let mut sessions: HashMap<Id, Record> = HashMap::new();
+        // A partial last page, and pages filled exactly.
+        for count in [5, 6] {
+            while sessions.len() < count {
+                let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+                save_session(&store, &session).await;
+                sessions.insert(session.id, session);
+            }
+            let streamed: HashMap<Id, Record> = store
+                .stream_all()
+                .await
+                .expect("Error streaming sessions")
+                .try_collect()
+                .await
+                .expect("Error streaming sessions");
+            assert_eq!(sessions, streamed, "Streamed sessions");
+            let report = store
+                .migrate_records()
+                .await
+                .expect("Error migrating records");
+            assert_eq!(count as u64, report.skipped, "Scanned sessions");
+        }
+    }
+
+    #[tokio::test]
+    async fn delete_missing() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        store
+            .delete(&Id::default())
+            .await
+            .expect("Deleting a missing session should succeed by default");
+
+        let store = store.with_error_on_missing_delete(true);
+        store
+            .delete(&Id::default())
+            .await
+            .expect_err("Deleting a missing session should fail when configured");
+        let session = make_record(None, [].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        store
+            .delete(&session.id)
+            .await
+            .expect("Deleting an existing session should succeed");
+    }
+
+    #[tokio::test]
+    async fn delete_returning() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+
+        let deleted = store
+            .delete_returning(&session.id)
+            .await
+            .expect("Error deleting session");
+        assert!(deleted, "Existing session should be deleted");
+        assert!(select_session(&db, &session).await.is_none());
+
+        let store = store.with_error_on_missing_delete(true);
+        let deleted = store
+            .delete_returning(&session.id)
+            .await
+            .expect("Deleting a missing session should not fail");
+        assert!(!deleted, "Missing session should not be deleted");
+    }
+
+    #[tokio::test]
+    async fn delete_backend_error() {
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        for error_on_missing_delete in [false, true] {
+            let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+                .with_error_on_missing_delete(error_on_missing_delete);
+            store
+                .delete(&Id::default())
+                .await
+                .expect_err("Deleting without a namespace should fail");
+        }
+    }
+
+    #[tokio::test]
+    async fn backend_error_labels() {
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session_id = Id::default();
+        let errors = [
+            ("load", store.load(&session_id).await.map(|_| ())),
+            ("delete", store.delete(&session_id).await),
+            ("delete_expired", store.delete_expired().await),
+        ];
+        for (operation, result) in errors {
+            let error = result.expect_err("Querying without a namespace should fail");
+            let Error::Backend(message) = error else {
+                panic!("Query failure should be a backend error: {error}");
+            };
+            assert!(
+                message.ends_with(&format!(
+                    "(operation: {operation}, table: {SESSIONS_TABLE})"
+                )),
+                "Error should be labeled with the operation: {message}"
+            );
+            assert!(
+                !message.contains(&session_id.to_string()),
+                "Error should not contain the session id: {message}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_timeout(Some(std::time::Duration::from_millis(50)));
+        let session = make_record(None, [].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+
+        // Stall every write to the table.
+        db.query(format!(
+            "define event stall on table {SESSIONS_TABLE} then (sleep(5s))"
+        ))
+        .await
+        .expect("Error defining event")
+        .check()
+        .expect("Error defining event");
+        let started = std::time::Instant::now();
+        let error = store
+            .save(&session)
+            .await
+            .expect_err("Stalled save should time out");
+        assert!(
+            started.elapsed() < std::time::Duration::from_secs(5),
+            "Save should not wait for the stalled write"
+        );
+        let Error::Backend(message) = error else {
+            panic!("Timeout should be a backend error: {error}");
+        };
+        assert!(
+            message.starts_with(SESSION_TIMEOUT),
+            "Error should report the timeout: {message}"
+        );
+
+        let error = store
+            .guarded(std::future::pending::<StoreResult<()>>)
+            .await
+            .expect_err("Pending operation should time out");
+        assert!(
+            error.to_string().contains(SESSION_TIMEOUT),
+            "Error should report the timeout: {error}"
+        );
+
+        let error = store
+            .save_many(&[session])
+            .await
+            .expect_err("Stalled batch save should time out");
+        assert!(
+            matches!(error, SurrealStoreError::Timeout(_)),
+            "Timeout should be a timeout error: {error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn skip_unchanged_saves() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_skip_unchanged_saves(true);
+        db.query(format!(
+            "define event count_writes on table {SESSIONS_TABLE} then (create writes)"
+        ))
+        .await
+        .expect("Error defining event")
+        .check()
+        .expect("Error defining event");
+        let writes = || async {
+            let count: Option<usize> = db
+                .query("count(select * from writes)")
+                .await
+                .expect("Error counting writes")
+                .take(0)
+                .expect("Error counting writes");
+            count.unwrap_or(0)
+        };
+
+        let mut session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        assert_eq!(1, writes().await, "New session should be written");
+        save_session(&store, &session).await;
+        assert_eq!(1, writes().await, "Identical save should not be written");
+
+        session.data.insert("key".to_string(), to_value("changed"));
+        save_session(&store, &session).await;
+        assert_eq!(2, writes().await, "Changed data should be written");
+
+        session.expiry_date += Duration::hours(1);
+        save_session(&store, &session).await;
+        assert_eq!(3, writes().await, "Changed expiry date should be written");
+        assert_eq!(Some(session.clone()), load_session(&store, &session).await);
+
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        save_session(&store, &session).await;
+        assert_eq!(4, writes().await, "Saves should be written by default");
+    }
+
+    #[tokio::test]
+    async fn load_metadata() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_user_id_key(Some("user_id".to_string()));
+        let session = make_record(None, [("user_id", "alice")].to_vec(), Duration::days(1));
+        let expired = make_record(None, [("user_id", "bob")].to_vec(), Duration::days(-1));
+        save_session(&store, &session).await;
+        save_session(&store, &expired).await;
+        // Data that fails to decode shows that it isn't decoded.
+        let corrupt = make_record(None, [].to_vec(), Duration::days(1));
+        let _: Option<SessionRecord> = db
+            .upsert((SESSIONS_TABLE, corrupt.id.to_string()))
+            .content(SessionRecord {
+                data: SessionData::MessagePack(vec![0x92, 0xc1]),
+                expiry_date: corrupt.expiry_date.unix_timestamp(),
+            })
+            .await
+            .expect("Error writing record");
+        store
+            .load(&corrupt.id)
+            .await
+            .expect_err("Corrupt session should fail to load");
+
+        let meta = store
+            .load_metadata(&session.id)
+            .await
+            .expect("Error loading metadata")
+            .expect("No metadata");
+        assert_eq!(
+            session.expiry_date.unix_timestamp(),
+            meta.expiry_date.unix_timestamp(),
+            "Expiry date"
+        );
+        assert_eq!(Some("alice"), meta.user_id.as_deref(), "User id");
+        assert!(!meta.pinned, "Session should not be pinned");
+
+        let meta = store
+            .load_metadata(&corrupt.id)
+            .await
+            .expect("Metadata should load without decoding the data")
+            .expect("No metadata");
+        assert_eq!(
+            corrupt.expiry_date.unix_timestamp(),
+            meta.expiry_date.unix_timestamp(),
+            "Expiry date"
+        );
+        assert_eq!(None, meta.user_id, "No user id");
+
+        for session_id in [expired.id, Id::default()] {
+            assert_eq!(
+                None,
+                store
+                    .load_metadata(&session_id)
+                    .await
+                    .expect("Error loading metadata"),
+                "Expired and missing sessions have no metadata"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn exists() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let live = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let expired = make_record(None, [("key", "value")].to_vec(), Duration::days(-1));
+        save_session(&store, &live).await;
+        save_session(&store, &expired).await;
+
+        for (session_id, exists, case) in [
+            (live.id, true, "Live session"),
+            (expired.id, false, "Expired session"),
+            (Id::default(), false, "Missing session"),
+        ] {
+            let result = store
+                .exists(&session_id)
+                .await
+                .expect("Error checking session");
+            assert_eq!(exists, result, "{case}");
+        }
+    }
+
+    #[tokio::test]
+    async fn ttl_remaining() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [].to_vec(), Duration::hours(1));
+        let expired = make_record(None, [].to_vec(), Duration::hours(-1));
+        save_session(&store, &session).await;
+        save_session(&store, &expired).await;
+
+        let expiry = store
+            .get_expiry(&session.id)
+            .await
+            .expect("Error getting expiry");
+        assert_eq!(
+            Some(session.expiry_date.unix_timestamp()),
+            expiry.map(|expiry| expiry.unix_timestamp()),
+            "Expiry date"
+        );
+
+        let remaining = store
+            .ttl_remaining(&session.id)
+            .await
+            .expect("Error getting TTL")
+            .expect("Session should have a TTL");
+        assert!(
+            remaining > Duration::minutes(59) && remaining <= Duration::hours(1),
+            "Remaining TTL {remaining} should be about an hour"
+        );
+
+        for id in [expired.id, Id::default()] {
+            let remaining = store.ttl_remaining(&id).await.expect("Error getting TTL");
+            assert!(remaining.is_none(), "Expired or missing session has no TTL");
+        }
+    }
+
+    #[tokio::test]
+    async fn touch() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::hours(1));
+        let expired = make_record(None, [].to_vec(), Duration::hours(-1));
+        save_session(&store, &session).await;
+        save_session(&store, &expired).await;
+
+        let expiry_date = OffsetDateTime::now_utc() + Duration::days(2);
+        store
+            .touch(&session.id, expiry_date)
+            .await
+            .expect("Error touching session");
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(session.data, loaded.data, "Data should be unchanged");
+        assert_eq!(
+            expiry_date.unix_timestamp(),
+            loaded.expiry_date.unix_timestamp(),
+            "Loaded expiry date"
+        );
+        assert_eq!(
+            Some(expiry_date.unix_timestamp()),
+            store
+                .get_expiry(&session.id)
+                .await
+                .expect("Error getting expiry")
+                .map(|expiry| expiry.unix_timestamp())
+        );
+
+        // Touching a missing or expired session does nothing.
+        let missing = Id::default();
+        for id in [expired.id, missing] {
+            store
+                .touch(&id, expiry_date)
+                .await
+                .expect("Touching a missing or expired session should succeed");
+        }
+        assert_eq!(None, load_session(&store, &expired).await);
+        assert_eq!(2, store.count_all().await.expect("Error counting"));
+    }
+
+    #[tokio::test]
+    async fn load_and_slide() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::hours(1));
+        let expired = make_record(None, [].to_vec(), Duration::hours(-1));
+        save_session(&store, &session).await;
+        save_session(&store, &expired).await;
+
+        let expiry_date = OffsetDateTime::now_utc() + Duration::days(2);
+        let slid = store
+            .load_and_slide(&session.id, expiry_date)
+            .await
+            .expect("Error sliding session")
+            .expect("No session");
+        assert_eq!(session.data, slid.data, "Data should be unchanged");
+        assert_eq!(
+            expiry_date.unix_timestamp(),
+            slid.expiry_date.unix_timestamp(),
+            "Returned expiry date"
+        );
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(slid, loaded, "The new expiry date should be stored");
+
+        let missing = Id::default();
+        for id in [expired.id, missing] {
+            assert_eq!(
+                None,
+                store
+                    .load_and_slide(&id, expiry_date)
+                    .await
+                    .expect("Error sliding session"),
+                "Missing and expired sessions should not be slid"
+            );
+        }
+        let (_, stored_expiry) = store
+            .load_raw(&expired.id)
+            .await
+            .expect("Error loading raw")
+            .expect("No expired session");
+        assert_eq!(
+            expired.expiry_date.unix_timestamp(),
+            stored_expiry.unix_timestamp(),
+            "An expired session should not be resurrected"
+        );
+        assert_eq!(None, load_session(&store, &expired).await);
+    }
+
+    #[tokio::test]
+    async fn audit_table() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_audit_table(Some("session_audit".to_string()));
+        let mut session = make_record(None, [].to_vec(), Duration::days(1));
+        create_session(&store, &mut session).await;
+        save_session(&store, &session).await;
+        store
+            .delete(&session.id)
+            .await
+            .expect("Error deleting session");
+
+        let operations: Vec<(String, String)> = db
+            .query("select value [operation, session] from session_audit order by timestamp")
+            .await
+            .expect("Error selecting audit rows")
+            .take(0)
+            .expect("Error taking audit rows");
+        let key = session.id.to_string();
+        assert_eq!(
+            vec![
+                ("create".to_string(), key.clone()),
+                ("save".to_string(), key.clone()),
+                ("delete".to_string(), key),
+            ],
+            operations,
+            "Audited operations"
+        );
+    }
+
+    #[tokio::test]
+    async fn audit_failure() {
+        let db = new_db_connection().await;
+        db.query(
+            "define table session_audit schemafull;
+define field operation on session_audit type int;",
+        )
+        .await
+        .expect("Error defining audit table");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_audit_table(Some("session_audit".to_string()));
+        let session = make_record(None, [].to_vec(), Duration::days(1));
+        let error = store
+            .save(&session)
+            .await
+            .expect_err("Save should fail when the audit row can't be written");
+        assert!(
+            error.to_string().contains("Audited session write failed"),
+            "Error should describe the audit failure: {error}"
+        );
+        assert!(
+            select_session(&db, &session).await.is_none(),
+            "Session should not be written without its audit row"
+        );
+    }
+
+    #[tokio::test]
+    async fn require_existing_table() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        store
+            .initialize()
+            .await
+            .expect("Missing table should be allowed by default");
+
+        let store = store.require_existing_table(true);
+        store
+            .initialize()
+            .await
+            .expect_err("Missing table should fail initialization");
+
+        db.query(format!("define table {SESSIONS_TABLE}"))
+            .await
+            .expect("Error defining table");
+        store
+            .initialize()
+            .await
+            .expect("Existing table should pass initialization");
+    }
+
+    #[tokio::test]
+    async fn clock_skew() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_clock_skew_warning(Some(std::time::Duration::from_secs(1)));
+        let skew = store
+            .check_clock_skew()
+            .await
+            .expect("Error checking clock skew");
+        assert!(
+            skew.abs() < Duration::seconds(1),
+            "A local engine should share the application clock: {skew}"
+        );
+        store
+            .initialize()
+            .await
+            .expect("Clock skew should only be warned about");
+    }
+
+    #[tokio::test]
+    async fn server_version() {
+        let db = new_db_connection().await;
+        let version = db.version().await.expect("Error getting version");
+        assert!(
+            version.major >= MIN_SURREALDB_MAJOR_VERSION,
+            "Tests should run on a supported version: {version}"
+        );
+        SurrealSessionStore::new(db, SESSIONS_TABLE.to_string())
+            .initialize()
+            .await
+            .expect("Supported version should pass initialization");
+
+        assert!(check_server_version(2, &"2.0.0").is_ok());
+        let error = check_server_version(1, &"1.5.4").expect_err("1.x should be rejected");
+        assert!(
+            matches!(error, SurrealStoreError::InvalidConfiguration(_)),
+            "Should be an invalid configuration error: {error}"
+        );
+        assert!(error.to_string().contains("1.5.4"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn value_roundtrip() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let values = serde_json::json!({
+            "null": null,
+            "bools": [true, false],
+            "integers": [0, -1, 1, i64::MIN, i64::MAX, u64::MAX, (i64::MAX as u64) + 1],
+            "floats": [0.0, -0.5, 1.0, f64::MIN, f64::MAX, f64::EPSILON, 1e300],
+            "strings": ["", "value", "ünïcödé"],
+            "nested": {"array": [[], [1, [2.5, {"deep": null}]]], "object": {}},
+        });
+        let mut session = make_record(None, [].to_vec(), Duration::days(1));
+        session.data = serde_json::from_value(values).expect("Error building data");
+
+        let decoded = make_session_record(&session)
+            .await
+            .to_session(&session.id, None)
+            .expect("Error decoding");
+        assert_eq!(session, decoded, "Decoded session");
+        for key in ["integers", "floats"] {
+            let original = session.data[key].as_array().expect("Array");
+            let decoded = decoded.data[key].as_array().expect("Array");
+            for (original, decoded) in original.iter().zip(decoded) {
+                assert_eq!(
+                    (original.is_u64(), original.is_i64(), original.is_f64()),
+                    (decoded.is_u64(), decoded.is_i64(), decoded.is_f64()),
+                    "Number {original} should keep its representation"
+                );
+            }
+        }
+
+        save_session(&store, &session).await;
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(session, loaded, "Loaded session");
+    }
+
+    #[tokio::test]
+    async fn circuit_breaker() {
+        // Operations fail until a namespace and database are selected.
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_circuit_breaker(Some(CircuitBreakerConfig {
+                failure_threshold: 2,
+                cooldown: std::time::Duration::from_millis(50),
+            }));
+        let id = Id::default();
+
+        for _ in 0..2 {
+            let error = store.load(&id).await.expect_err("Load should fail");
+            assert!(
+                !error.to_string().contains("Circuit breaker"),
+                "Failures below the threshold reach the database: {error}"
+            );
+        }
+        let error = store.load(&id).await.expect_err("Load should fail");
+        assert!(
+            error.to_string().contains("Circuit breaker"),
+            "Circuit should be open: {error}"
+        );
+        let error = store.exists(&id).await.expect_err("Exists should fail");
+        assert!(
+            error.to_string().contains("Circuit breaker"),
+            "Reads besides loads should be guarded too: {error}"
+        );
+        let error = store.load_raw(&id).await.expect_err("Raw load should fail");
+        assert!(
+            error.to_string().contains("Circuit breaker"),
+            "Raw loads should be guarded too: {error}"
+        );
+        let error = store
+            .load_including_expired(&id)
+            .await
+            .expect_err("Load including expired should fail");
+        assert!(
+            error.to_string().contains("Circuit breaker"),
+            "Loads including expired sessions should be guarded too: {error}"
+        );
+        let error = store
+            .load_metadata(&id)
+            .await
+            .expect_err("Metadata load should fail");
+        assert!(
+            error.to_string().contains("Circuit breaker"),
+            "Metadata loads should be guarded too: {error}"
+        );
+        let error = store.count_all().await.expect_err("Count should fail");
+        assert!(
+            error.to_string().contains("Circuit breaker"),
+            "Counts should be guarded too: {error}"
+        );
+        let error = store
+            .get_expiry(&id)
+            .await
+            .expect_err("Expiry lookup should fail");
+        assert!(
+            error.to_string().contains("Circuit breaker"),
+            "Expiry lookups should be guarded too: {error}"
+        );
+
+        db.use_ns("testing")
+            .use_db("testing")
+            .await
+            .expect("Error selecting database");
+        store
+            .load(&id)
+            .await
+            .expect_err("Circuit should stay open during the cooldown");
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+        store.load(&id).await.expect("Probe should succeed");
+        store.load(&id).await.expect("Circuit should be closed");
+    }
+
+    #[tokio::test]
+    async fn sign_in_again() {
+        let root = surrealdb::opt::auth::Root {
+            username: "root",
+            password: "secret",
+        };
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(
+            surrealdb::opt::Config::default().user(root),
+        )
+        .await
+        .expect("Surreal initialization failure");
+        db.signin(root).await.expect("Error signing in");
+        db.use_ns("testing")
+            .use_db("testing")
+            .await
+            .expect("Error selecting database");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        // Fails with an expired session on the first attempt only.
+        let expiring = |attempts: &std::sync::atomic::AtomicU32| {
+            let attempt = attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            async move {
+                match attempt {
+                    0 => Err(SurrealStoreError::connection(
+                        "There was a problem with the database: The session has expired",
+                    )),
+                    _ => Ok(attempt),
+                }
+            }
+        };
+
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        store
+            .guarded(|| expiring(&attempts))
+            .await
+            .expect_err("Without credentials the failure should be returned");
+        assert_eq!(1, attempts.into_inner(), "Attempts without credentials");
+
+        let store = store.with_credentials(Some(Credentials::Root {
+            username: "root".to_string(),
+            password: "secret".to_string(),
+        }));
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result = store.guarded(|| expiring(&attempts)).await;
+        assert_eq!(
+            1,
+            result.expect("Operation should succeed after signing in again"),
+            "Attempts"
+        );
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        assert_eq!(Some(session.clone()), load_session(&store, &session).await);
+
+        let store = store.with_credentials(Some(Credentials::Root {
+            username: "root".to_string(),
+            password: "wrong".to_string(),
+        }));
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let error = store
+            .guarded(|| expiring(&attempts))
+            .await
+            .expect_err("Signing in with wrong credentials should fail");
+        assert!(
+            error.to_string().contains("Signing in again failed"),
+            "The sign in failure should be returned: {error}"
+        );
+        assert_eq!(1, attempts.into_inner(), "Attempts with wrong credentials");
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        // Operations fail until a namespace and database are selected.
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string()).with_retry(
+            Some(RetryPolicy {
+                max_attempts: 3,
+                base_delay: std::time::Duration::from_millis(50),
+            }),
+        );
+        let select_database = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            db.use_ns("testing")
+                .use_db("testing")
+                .await
+                .expect("Error selecting database");
+        });
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        store
+            .save(&session)
+            .await
+            .expect("Save should succeed once the database is selected");
+        select_database.await.expect("Error selecting database");
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(session, loaded, "Saved session");
+
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result = store
+            .guarded(|| {
+                let attempt = attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async move {
+                    match attempt {
+                        0 => Err(SurrealStoreError::connection("Connection lost")),
+                        _ => Ok(attempt),
+                    }
+                }
+            })
+            .await;
+        assert_eq!(1, result.expect("Retry should succeed"), "Attempts");
+
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        store
+            .guarded(|| {
+                attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async {
+                    Err::<(), _>(SurrealStoreError::Serialization(Error::Decode(
+                        "Corrupt session".to_string(),
+                    )))
+                }
+            })
+            .await
+            .expect_err("Decode errors should fail");
+        assert_eq!(
+            1,
+            attempts.into_inner(),
+            "Decode errors should not be retried"
+        );
+
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        store
+            .guarded(|| {
+                attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async { Err::<(), _>(SurrealStoreError::connection("Connection lost")) }
+            })
+            .await
+            .expect_err("Persistent failures should fail");
+        assert_eq!(3, attempts.into_inner(), "Attempts are limited");
+    }
+
+    #[tokio::test]
+    async fn replace_data() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(
+            None,
+            [("old key", "old value"), ("shared key", "old value")].to_vec(),
+            Duration::hours(1),
+        );
+        save_session(&store, &session).await;
+
+        let data = HashMap::from([("shared key".to_string(), to_value("new value"))]);
+        let replaced = store
+            .replace_data(&session.id, data.clone())
+            .await
+            .expect("Error replacing data")
+            .expect("Session should be updated");
+        let expected = Record {
+            data,
+            ..session.clone()
+        };
+        assert_eq!(expected, replaced, "Returned session");
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(expected, loaded, "Loaded session");
+
+        let expired = make_record(None, [].to_vec(), Duration::hours(-1));
+        save_session(&store, &expired).await;
+        for id in [expired.id, Id::default()] {
+            let replaced = store
+                .replace_data(&id, HashMap::new())
+                .await
+                .expect("Error replacing data");
+            assert!(replaced.is_none(), "Missing session should not be updated");
+        }
+        let record = select_session(&db, &expired)
+            .await
+            .expect("Expired session record");
+        assert_eq!(
+            make_session_record(&expired).await,
+            record,
+            "Expired session should be unchanged"
+        );
+    }
+
+    #[tokio::test]
+    async fn migrate_on_read() {
+        let db = new_db_connection().await;
+        for migrate_on_read in [false, true] {
+            let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+                .with_migrate_on_read(migrate_on_read);
+            let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+            let legacy = SessionRecord {
+                data: SessionData::MessagePack(
+                    rmp_serde::to_vec(&session).expect("Error encoding"),
+                ),
+                expiry_date: session.expiry_date.unix_timestamp(),
+            };
+            assert!(legacy.is_legacy_encoding(), "Positional encoding is legacy");
+            let _: Option<SessionRecord> = db
+                .upsert((SESSIONS_TABLE, session.id.to_string()))
+                .content(legacy)
+                .await
+                .expect("Error writing legacy record");
+
+            let loaded = load_session(&store, &session).await.expect("No session");
+            assert_eq!(session, loaded, "Loaded legacy session");
+
+            let record = select_session(&db, &session).await.expect("No record");
+            assert_eq!(
+                !migrate_on_read,
+                record.is_legacy_encoding(),
+                "Record should be migrated only when enabled"
+            );
+            if migrate_on_read {
+                assert_eq!(
+                    make_session_record(&session).await,
+                    record,
+                    "Migrated record"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn migrate_records() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let legacy = SessionRecord {
+            data: SessionData::MessagePack(rmp_serde::to_vec(&session).expect("Error encoding")),
+            expiry_date: session.expiry_date.unix_timestamp(),
+        };
+        let json = make_record(None, [("key", "json")].to_vec(), Duration::days(1));
+        let current = make_record(None, [("key", "current")].to_vec(), Duration::days(1));
+        let invalid = make_record(None, [].to_vec(), Duration::days(1));
+        let invalid_record = SessionRecord {
+            data: SessionData::MessagePack(vec![0x92, 0xc1]),
+            expiry_date: invalid.expiry_date.unix_timestamp(),
+        };
+        let json_store = store
+            .clone()
+            .with_serialization_format(SerializationFormat::Json);
+        save_session(&json_store, &json).await;
+        save_session(&store, &current).await;
+        for (id, record) in [(session.id, legacy), (invalid.id, invalid_record)] {
+            let _: Option<SessionRecord> = db
+                .upsert((SESSIONS_TABLE, id.to_string()))
+                .content(record)
+                .await
+                .expect("Error writing record");
+        }
+
+        let report = store
+            .migrate_records()
+            .await
+            .expect("Error migrating records");
+        assert_eq!(
+            MigrationReport {
+                migrated: 2,
+                skipped: 1,
+                failed: vec![invalid.id.to_string()],
+            },
+            report
+        );
+        for session in [&session, &json] {
+            let record = select_session(&db, session).await.expect("No record");
+            assert!(!record.is_legacy_encoding(), "Record should be migrated");
+            assert_eq!(SerializationFormat::MessagePack, record.data.format());
+            let loaded = load_session(&store, session).await.expect("No session");
+            assert_eq!(session, &loaded, "Migrated session");
+        }
+
+        let report = store
+            .migrate_records()
+            .await
+            .expect("Error migrating records");
+        assert_eq!(0, report.migrated, "Nothing left to migrate");
+        assert_eq!(3, report.skipped);
+    }
+
+    #[tokio::test]
+    async fn max_decode_size() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_max_decode_size(Some(200));
+        let large_value = "value".repeat(100);
+        let large = make_record(
+            None,
+            [("key", large_value.as_str())].to_vec(),
+            Duration::days(1),
+        );
+        let small = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        save_session(&store, &large).await;
+        save_session(&store, &small).await;
+
+        let error = store
+            .load(&large.id)
+            .await
+            .expect_err("Oversized session should not be decoded");
+        assert!(
+            matches!(error, Error::Decode(_)),
+            "Oversized session should be a decode error: {error}"
+        );
+        let loaded = load_session(&store, &small).await;
+        assert_eq!(Some(small), loaded, "Small session should load");
+    }
+
+    #[tokio::test]
+    async fn decode_failure_policy() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let corrupt = make_record(None, [].to_vec(), Duration::days(1));
+        let _: Option<SessionRecord> = db
+            .upsert((SESSIONS_TABLE, corrupt.id.to_string()))
+            .content(SessionRecord {
+                data: SessionData::MessagePack(vec![0x92, 0xc1]),
+                expiry_date: corrupt.expiry_date.unix_timestamp(),
+            })
+            .await
+            .expect("Error writing record");
+
+        let error = store
+            .load(&corrupt.id)
+            .await
+            .expect_err("Corrupt session should fail to load by default");
+        assert!(
+            matches!(error, Error::Decode(_)),
+            "Corrupt session should be a decode error: {error}"
+        );
+
+        let keeping = store
+            .clone()
+            .with_decode_failure_policy(DecodeFailure::TreatAsMissing { delete: false });
+        assert_eq!(
+            None,
+            load_session(&keeping, &corrupt).await,
+            "Corrupt session should be treated as missing"
+        );
+        assert!(
+            select_session(&db, &corrupt).await.is_some(),
+            "Corrupt session should be kept"
+        );
+
+        let deleting = store
+            .clone()
+            .with_decode_failure_policy(DecodeFailure::TreatAsMissing { delete: true });
+        assert_eq!(
+            None,
+            load_session(&deleting, &corrupt).await,
+            "Corrupt session should be treated as missing"
+        );
+        assert!(
+            select_session(&db, &corrupt).await.is_none(),
+            "Corrupt session should be deleted"
+        );
+
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        save_session(&deleting, &session).await;
+        assert_eq!(
+            Some(session.clone()),
+            load_session(&deleting, &session).await,
+            "Valid sessions should still load"
+        );
+    }
+
+    #[tokio::test]
+    async fn max_data_bytes() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_max_data_bytes(Some(200));
+        let large_value = "value".repeat(100);
+        let mut large = make_record(
+            None,
+            [("key", large_value.as_str())].to_vec(),
+            Duration::days(1),
+        );
+        let small = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+
+        let error = store
+            .save(&large)
+            .await
+            .expect_err("Oversized session should not be saved");
+        assert!(
+            matches!(error, Error::Encode(_)),
+            "Oversized session should be an encode error: {error}"
+        );
+        let error = store
+            .create(&mut large)
+            .await
+            .expect_err("Oversized session should not be created");
+        assert!(
+            matches!(error, Error::Encode(_)),
+            "Oversized session should be an encode error: {error}"
+        );
+        assert_eq!(None, select_session(&db, &large).await, "No row written");
+        save_session(&store, &small).await;
+        assert_eq!(1, store.count_all().await.expect("Error counting"));
+    }
+
+    #[tokio::test]
+    async fn validator() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_validator(
+                |session| match session.data.keys().find(|key| *key != "user_id") {
+                    Some(key) => Err(format!("Unexpected key {key}")),
+                    None => Ok(()),
+                },
+            );
+        let mut invalid = make_record(None, [("typo_id", "1")].to_vec(), Duration::days(1));
+        let valid = make_record(None, [("user_id", "1")].to_vec(), Duration::days(1));
+
+        let error = store
+            .save(&invalid)
+            .await
+            .expect_err("Invalid session should not be saved");
+        assert!(
+            matches!(&error, Error::Encode(message)
+                if message.starts_with(SESSION_INVALID) && message.contains("typo_id")),
+            "Invalid session should be a validation error: {error}"
+        );
+        store
+            .create(&mut invalid)
+            .await
+            .expect_err("Invalid session should not be created");
+        let error = store
+            .create_strict(&invalid)
+            .await
+            .expect_err("Invalid session should not be created");
+        assert!(
+            matches!(error, SurrealStoreError::Validation(_)),
+            "Should be a validation error: {error}"
+        );
+        assert_eq!(None, select_session(&db, &invalid).await, "No row written");
+        assert_eq!(0, store.count_all().await.expect("Error counting"));
+
+        save_session(&store, &valid).await;
+        assert_eq!(Some(valid.clone()), load_session(&store, &valid).await);
+    }
+
+    #[tokio::test]
+    async fn error_kinds() {
+        // Operations fail until a namespace and database are selected.
+        let unscoped = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        let store = SurrealSessionStore::new(unscoped, SESSIONS_TABLE.to_string());
+        let mut session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let errors = [
+            store
+                .create(&mut session)
+                .await
+                .expect_err("Create should fail"),
+            store.save(&session).await.expect_err("Save should fail"),
+            store.load(&session.id).await.expect_err("Load should fail"),
+            store
+                .delete(&session.id)
+                .await
+                .expect_err("Delete should fail"),
+        ];
+        for error in errors {
+            assert!(
+                matches!(error, Error::Backend(_)),
+                "Connection failures should be backend errors: {error}"
+            );
+        }
+
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        for data in ["<bytes>'not a session'", "5"] {
+            let id = Id::default();
+            db.query(format!(
+                "upsert type::thing($table, $id)
+content {{ data: {data}, expiry_date: time::unix(time::now()) + 3600 }}"
+            ))
+            .bind(("table", SESSIONS_TABLE))
+            .bind(("id", id.to_string()))
+            .await
+            .expect("Error storing session")
+            .check()
+            .expect("Error storing session");
+            let error = store.load(&id).await.expect_err("Load should fail");
+            assert!(
+                matches!(error, Error::Decode(_)),
+                "Malformed sessions should be decode errors: {data}: {error}"
+            );
+        }
+
+        let serialization = || {
+            surrealdb::Error::Db(surrealdb::error::Db::Serialization(
+                "Invalid value".to_string(),
+            ))
+        };
+        assert!(matches!(
+            read_error(serialization()),
+            SurrealStoreError::Serialization(Error::Decode(_))
+        ));
+        assert!(matches!(
+            write_error(serialization()),
+            SurrealStoreError::Serialization(Error::Encode(_))
+        ));
+        let backend = || surrealdb::Error::Api(surrealdb::error::Api::ConnectionUninitialised);
+        assert!(matches!(
+            read_error(backend()),
+            SurrealStoreError::Connection(Error::Backend(_))
+        ));
+        assert!(matches!(
+            write_error(backend()),
+            SurrealStoreError::Connection(Error::Backend(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn load_consistent() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("user", "1")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        db.query("create users:1 set name = 'Alice'")
+            .await
+            .expect("Error creating user");
+
+        let (loaded, mut response) = store
+            .load_consistent(
+                &session.id,
+                "select value name from type::thing('users', $user)",
+                |query| query.bind(("user", 1)),
+            )
+            .await
+            .expect("Error loading session");
+        assert_eq!(Some(session), loaded, "Loaded session");
+        let names: Vec<String> = response.take(1).expect("Error taking names");
+        assert_eq!(vec!["Alice".to_string()], names, "Related results");
+    }
+
+    #[tokio::test]
+    async fn ensure_table() {
+        #[derive(Deserialize)]
+        struct TableInfo {
+            indexes: BTreeMap<String, String>,
+        }
+
+        async fn indexes(db: &Surreal<DB>) -> BTreeMap<String, String> {
+            let info: Option<TableInfo> = db
+                .query(format!("info for table {SESSIONS_TABLE}"))
+                .await
+                .expect("Error getting table info")
+                .take(0)
+                .expect("Error taking table info");
+            info.expect("No table info").indexes
+        }
+
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        store.ensure_table().await.expect("Error ensuring table");
+        store
+            .ensure_table()
+            .await
+            .expect("Ensuring table is idempotent");
+        let definition = indexes(&db)
+            .await
+            .remove("sessions_expiry_date")
+            .expect("Default index should be defined");
+        assert!(
+            definition.contains("FIELDS expiry_date"),
+            "Index should be on expiry_date: {definition}"
+        );
+
+        let db = new_db_connection().await;
+        SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_expiry_index(Some("by_expiry".to_string()))
+            .ensure_table()
+            .await
+            .expect("Error ensuring table");
+        assert_eq!(
+            vec!["by_expiry".to_string()],
+            indexes(&db).await.into_keys().collect::<Vec<_>>(),
+            "Custom index"
+        );
+
+        let db = new_db_connection().await;
+        SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_expiry_index(None)
+            .ensure_table()
+            .await
+            .expect("Error ensuring table");
+        assert!(indexes(&db).await.is_empty(), "No index");
+
+        SurrealSessionStore {
+            session_table: "sessions; remove table users".to_string(),
+            ..SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+        }
+        .ensure_table()
+        .await
+        .expect_err("Invalid table name should be rejected");
+    }
+
+    #[tokio::test]
+    async fn check_table_access() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let full = TableAccessReport {
+            select: true,
+            create: true,
+            update: true,
+            delete: true,
+        };
+        let report = store
+            .check_table_access()
+            .await
+            .expect("Error checking access");
+        assert_eq!(full, report, "Root access");
+        assert_eq!(0, store.count_all().await.expect("Error counting"));
+
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        db.query(format!(
+            "define namespace testing; use ns testing; define database testing; use db testing;
+define table user permissions full;
+define access account on database type record
+    signup (create user set name = $name)
+    signin (select * from user where name = $name);
+define table {SESSIONS_TABLE} permissions
+    for select, create full
+    for update, delete none;"
+        ))
+        .await
+        .expect("Error defining tables")
+        .check()
+        .expect("Error defining tables");
+        db.signup(surrealdb::opt::auth::Record {
+            namespace: "testing",
+            database: "testing",
+            access: "account",
+            params: serde_json::json!({ "name": "restricted" }),
+        })
+        .await
+        .expect("Error signing up");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let report = store
+            .check_table_access()
+            .await
+            .expect("Error checking access");
+        assert_eq!(
+            TableAccessReport {
+                update: false,
+                delete: false,
+                ..full
+            },
+            report,
+            "Restricted access"
+        );
+    }
+
+    #[tokio::test]
+    async fn for_table() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        assert!(
+            store.for_table("admin sessions").is_err(),
+            "Invalid table name should be rejected"
+        );
+        let admin_store = store
+            .for_table("admin_sessions")
+            .expect("Error creating store");
+        assert_eq!("admin_sessions", admin_store.session_table());
+        let session = make_record(None, [("key", "user")].to_vec(), Duration::days(1));
+        let admin_session = Record {
+            data: [("key".to_string(), to_value("admin"))].into(),
+            ..session.clone()
+        };
+        save_session(&store, &session).await;
+        save_session(&admin_store, &admin_session).await;
+
+        assert_eq!(Some(session.clone()), load_session(&store, &session).await);
+        assert_eq!(
+            Some(admin_session.clone()),
+            load_session(&admin_store, &admin_session).await
+        );
+        store.delete(&session.id).await.expect("Error deleting");
+        assert_eq!(None, load_session(&store, &session).await);
+        assert_eq!(
+            Some(admin_session.clone()),
+            load_session(&admin_store, &admin_session).await,
+            "Deleting from one table should not affect the other"
+        );
+    }
+
+    #[tokio::test]
+    async fn new_default() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new_default(db.clone());
+        assert_eq!(DEFAULT_SESSION_TABLE, store.session_table());
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        let stored: Option<SessionRecord> = db
+            .select(("sessions", session.id.to_string()))
+            .await
+            .expect("Error selecting session");
+        assert!(stored.is_some(), "Session should be in the sessions table");
+    }
+
+    #[tokio::test]
+    async fn table_name_validation() {
+        let db = new_db_connection().await;
+        for valid in ["sessions", "Sessions_2", "_sessions"] {
+            let store = SurrealSessionStore::try_new(db.clone(), valid.to_string())
+                .unwrap_or_else(|e| panic!("Table name {valid} should be valid: {e}"));
+            save_session(&store, &make_record(None, [].to_vec(), Duration::days(1))).await;
+        }
+        for invalid in ["", "my-sessions", "sessions; remove table users", "séances"] {
+            assert!(
+                SurrealSessionStore::try_new(db.clone(), invalid.to_string()).is_err(),
+                "Table name {invalid:?} should be rejected"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn connect() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::connect(db.clone(), SESSIONS_TABLE.to_string())
+            .await
+            .expect("Connecting a scoped client should succeed");
+        save_session(&store, &make_record(None, [].to_vec(), Duration::days(1))).await;
+        SurrealSessionStore::connect(db, SESSIONS_TABLE.to_string())
+            .await
+            .expect("Connecting to an existing table should succeed");
+
+        let unscoped = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        SurrealSessionStore::connect(unscoped, SESSIONS_TABLE.to_string())
+            .await
+            .expect_err("Connecting without a namespace and database should fail");
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Invalid session table name")]
+    async fn new_with_invalid_table_name() {
+        let db = new_db_connection().await;
+        SurrealSessionStore::new(db, "my-sessions".to_string());
+    }
+
+    #[tokio::test]
+    async fn expiry_queries_use_index() {
+        #[derive(Deserialize)]
+        struct Step {
+            operation: String,
+        }
+
+        async fn plan(db: &Surreal<DB>) -> Vec<String> {
+            let steps: Vec<Step> = db
+                .query(format!(
+                    "select id from {SESSIONS_TABLE}
+where expiry_date <= time::unix(time::now()) explain"
+                ))
+                .await
+                .expect("Error explaining query")
+                .take(0)
+                .expect("Error taking plan");
+            steps.into_iter().map(|step| step.operation).collect()
+        }
+
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        for i in 0..1000 {
+            let offset = if i % 2 == 0 {
+                Duration::days(-1)
+            } else {
+                Duration::days(1)
+            };
+            save_session(&store, &make_record(None, [].to_vec(), offset)).await;
+        }
+        assert!(
+            !plan(&db).await.contains(&"Iterate Index".to_string()),
+            "Expired sessions are found by a scan without the index"
+        );
+
+        store.ensure_table().await.expect("Error ensuring table");
+        assert!(
+            plan(&db).await.contains(&"Iterate Index".to_string()),
+            "Expired sessions should be found through the index"
+        );
+        assert_eq!(
+            500,
+            store
+                .delete_expired_count()
+                .await
+                .expect("Error deleting expired"),
+            "Deleted through the index"
+        );
+    }
+
+    #[tokio::test]
+    async fn schema_mismatch_error() {
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(
+            surrealdb::opt::Config::default().strict(),
+        )
+        .await
+        .expect("Surreal initialization failure");
+        db.query(format!(
+            "define namespace testing; use ns testing; define database testing; use db testing;
+define table {SESSIONS_TABLE} schemafull;
+define field data on table {SESSIONS_TABLE} type array<int>;
+define field expiry_date on table {SESSIONS_TABLE} type string;"
+        ))
+        .await
+        .expect("Error defining table")
+        .check()
+        .expect("Error defining table");
+        db.use_ns("testing")
+            .use_db("testing")
+            .await
+            .expect("Surreal database initialization failure");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [].to_vec(), Duration::days(1));
+
+        let error = store
+            .save(&session)
+            .await
+            .expect_err("Saving a mismatched field should fail");
+        let Error::Backend(message) = error else {
+            panic!("Mismatched field should be a backend error: {error}");
+        };
+        assert!(
+            !message.contains(SESSION_NOT_SAVED),
+            "The database error should not be masked: {message}"
+        );
+        assert!(
+            message.contains(SESSIONS_TABLE) && message.contains("expiry_date"),
+            "Error should name the table and field: {message}"
+        );
+
+        let error = store
+            .save_many(&[session])
+            .await
+            .expect_err("Saving a mismatched field should fail");
+        assert!(
+            matches!(error, SurrealStoreError::SchemaMismatch(_)),
+            "Mismatched field should be a schema mismatch error: {error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn save_error_omits_session_id() {
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(
+            surrealdb::opt::Config::default().strict(),
+        )
+        .await
+        .expect("Surreal initialization failure");
+        db.query(format!(
+            "define namespace testing; use ns testing; define database testing; use db testing;
+define table {SESSIONS_TABLE} schemafull;
+define field data on table {SESSIONS_TABLE} type array<int>;
+define field expiry_date on table {SESSIONS_TABLE} type string;"
+        ))
+        .await
+        .expect("Error defining table")
+        .check()
+        .expect("Error defining table");
+        db.use_ns("testing")
+            .use_db("testing")
+            .await
+            .expect("Surreal database initialization failure");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [].to_vec(), Duration::days(1));
+        let session_id = session.id.to_string();
+
+        let errors = [
+            store
+                .save(&session)
+                .await
+                .expect_err("Saving a mismatched field should fail")
+                .to_string(),
+            store
+                .save_many(&[session.clone()])
+                .await
+                .expect_err("Saving a mismatched field should fail")
+                .to_string(),
+        ];
+        for message in errors {
+            assert!(
+                !message.contains(&session_id),
+                "Error should not contain the session id: {message}"
+            );
+        }
+
+        let error = store
+            .confirm_saved(store.record_key(&session.id), 0)
+            .await
+            .expect_err("Missing record should not be confirmed");
+        assert!(
+            !error.to_string().contains(&session_id),
+            "Error should not contain the session id: {error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn store_error_variants() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let invalid = [
+            store.clone().with_page_size(0).map(|_| ()),
+            store.for_table("admin sessions").map(|_| ()),
+            store.rekey_prefix("a", "ab").await.map(|_| ()),
+            store.delete_by_user("user").await.map(|_| ()),
+        ];
+        for result in invalid {
+            let error = result.expect_err("Invalid configuration should fail");
+            assert!(
+                matches!(error, SurrealStoreError::InvalidConfiguration(_)),
+                "Should be an invalid configuration error: {error}"
+            );
+        }
+
+        let error = store
+            .clone()
+            .require_existing_table(true)
+            .initialize()
+            .await
+            .expect_err("Missing table should fail");
+        assert!(
+            matches!(error, SurrealStoreError::NotFound(_)),
+            "Missing table should be a not found error: {error}"
+        );
+
+        let large_value = "value".repeat(100);
+        let large = make_record(
+            None,
+            [("key", large_value.as_str())].to_vec(),
+            Duration::days(1),
+        );
+        let error = store
+            .clone()
+            .with_max_data_bytes(Some(200))
+            .save_many(&[large])
+            .await
+            .expect_err("Oversized session should not be saved");
+        assert!(
+            matches!(error, SurrealStoreError::Serialization(Error::Encode(_))),
+            "Oversized session should be a serialization error: {error}"
+        );
+        assert!(
+            matches!(Error::from(error), Error::Encode(_)),
+            "Converting should keep the trait error"
+        );
+
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        let store = SurrealSessionStore::new(db, SESSIONS_TABLE.to_string());
+        let error = store
+            .count_all()
+            .await
+            .expect_err("Querying without a namespace should fail");
+        assert!(
+            matches!(error, SurrealStoreError::Connection(Error::Backend(_))),
+            "Query failure should be a connection error: {error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn define_table() {
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(
+            surrealdb::opt::Config::default().strict(),
+        )
+        .await
+        .expect("Surreal initialization failure");
+        db.query("define namespace testing; use ns testing; define database testing")
+            .await
+            .expect("Error defining database")
+            .check()
+            .expect("Error defining database");
+        db.use_ns("testing")
+            .use_db("testing")
+            .await
+            .expect("Surreal database initialization failure");
+        let mut session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        // Native data stores the expiry date to the second.
+        session.expiry_date = session
+            .expiry_date
+            .replace_nanosecond(0)
+            .expect("Error truncating expiry");
+
+        for format in [
+            SerializationFormat::MessagePack,
+            SerializationFormat::Json,
+            SerializationFormat::Native,
+        ] {
+            let store = SurrealSessionStore::new(db.clone(), format!("sessions_{format:?}"))
+                .with_serialization_format(format);
+            assert!(
+                store.save(&session).await.is_err(),
+                "Saving to an undefined table should fail in strict mode"
+            );
+            store.define_table().await.expect("Error defining table");
+            store
+                .define_table()
+                .await
+                .expect("Defining the table is idempotent");
+            save_session(&store, &session).await;
+            let loaded = load_session(&store, &session).await.expect("No session");
+            assert_eq!(session, loaded, "Loaded session: {format:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn load_with_raw() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+
+        let (loaded, raw) = store
+            .load_with_raw(&session.id)
+            .await
+            .expect("Error loading session")
+            .expect("No session");
+        assert_eq!(session, loaded, "Loaded session");
+        assert_eq!(
+            make_session_record(&session).await.data.as_bytes(),
+            raw,
+            "Raw bytes"
+        );
+
+        let missing = store
+            .load_with_raw(&Id::default())
+            .await
+            .expect("Error loading session");
+        assert!(missing.is_none(), "Missing session");
+    }
+
+    #[tokio::test]
+    async fn load_raw() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let expired = make_record(None, [("key", "expired")].to_vec(), Duration::days(-1));
+        save_session(&store, &session).await;
+        save_session(&store, &expired).await;
+
+        for session in [&session, &expired] {
+            let (raw, expiry_date) = store
+                .load_raw(&session.id)
+                .await
+                .expect("Error loading raw session")
+                .expect("No session");
+            assert_eq!(
+                make_session_record(session).await.data.as_bytes(),
+                raw,
+                "Raw bytes"
+            );
+            assert_eq!(
+                session.expiry_date.unix_timestamp(),
+                expiry_date.unix_timestamp(),
+                "Stored expiry date"
+            );
+        }
+
+        let missing = store
+            .load_raw(&Id::default())
+            .await
+            .expect("Error loading raw session");
+        assert!(missing.is_none(), "Missing session");
+    }
+
+    #[tokio::test]
+    async fn load_including_expired() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let expired = make_record(None, [("key", "expired")].to_vec(), Duration::days(-1));
+        save_session(&store, &session).await;
+        save_session(&store, &expired).await;
+
+        for session in [&session, &expired] {
+            let loaded = store
+                .load_including_expired(&session.id)
+                .await
+                .expect("Error loading session");
+            assert_eq!(Some(session), loaded.as_ref(), "Loaded including expired");
+        }
+        assert_eq!(
+            None,
+            load_session(&store, &expired).await,
+            "Expired session should not load normally"
+        );
+        let missing = store
+            .load_including_expired(&Id::default())
+            .await
+            .expect("Error loading session");
+        assert!(missing.is_none(), "Missing session");
+    }
+
+    #[tokio::test]
+    async fn confirm_saved() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [].to_vec(), Duration::days(1));
+        let key = session.id.to_string();
+        let expiry_date = session.expiry_date.unix_timestamp();
+
+        store
+            .confirm_saved(key.clone(), expiry_date)
+            .await
+            .expect_err("Missing record should not be confirmed");
+        upsert_raw(&db, &key, &session).await;
+        store
+            .confirm_saved(key.clone(), expiry_date)
+            .await
+            .expect("Written record should be confirmed");
+        store
+            .confirm_saved(key, expiry_date + 1)
+            .await
+            .expect_err("Record with another expiry should not be confirmed");
+    }
+
+    #[tokio::test]
+    async fn save_and_confirm() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        store
+            .save_and_confirm(&session)
+            .await
+            .expect("Error saving session");
+        assert_eq!(Some(session.clone()), load_session(&store, &session).await);
+
+        // The event deletes the expired session right after the write.
+        store
+            .define_expiry_event()
+            .await
+            .expect("Error defining event");
+        let removed = make_record(None, [].to_vec(), Duration::days(-1));
+        save_session(&store, &removed).await;
+        let error = store
+            .save_and_confirm(&removed)
+            .await
+            .expect_err("Removed session should not be confirmed");
+        let SurrealStoreError::NotFound(Error::Backend(message)) = error else {
+            panic!("Unconfirmed save should be a not found error: {error}");
+        };
+        assert!(
+            message.starts_with(SESSION_NOT_SAVED),
+            "Error should report the unconfirmed save: {message}"
+        );
+    }
+
+    #[tokio::test]
+    async fn pinned_sessions() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let pinned = make_record(None, [].to_vec(), Duration::days(-1));
+        let unpinned = make_record(None, [].to_vec(), Duration::days(-1));
+        store
+            .save_pinned(&pinned, true)
+            .await
+            .expect("Error saving pinned session");
+        store
+            .save_pinned(&unpinned, false)
+            .await
+            .expect("Error saving unpinned session");
+
+        store
+            .delete_expired()
+            .await
+            .expect("Error deleting expired sessions");
+        assert!(
+            store.record_exists(&pinned.id).await,
+            "Pinned session should survive cleanup"
+        );
+        assert!(
+            !store.record_exists(&unpinned.id).await,
+            "Unpinned session should be deleted"
+        );
+
+        // A plain save keeps the pin, and unpinning exposes the session
+        // to cleanup again.
+        save_session(&store, &pinned).await;
+        assert_eq!(
+            0,
+            store.delete_expired_count().await.expect("Error deleting")
+        );
+        store
+            .save_pinned(&pinned, false)
+            .await
+            .expect("Error unpinning session");
+        assert_eq!(
+            1,
+            store.delete_expired_count().await.expect("Error deleting")
+        );
+    }
+
+    #[tokio::test]
+    async fn cleanup_per_table() {
+        let db = new_db_connection().await;
+        let tables = ["sessions_fast", "sessions_slow", "sessions_default"];
+        let mut expired = Vec::new();
+        for table in tables {
+            let store = SurrealSessionStore::new(db.clone(), table.to_string());
+            let session = make_record(None, [].to_vec(), Duration::days(-1));
+            save_session(&store, &session).await;
+            expired.push((store, session));
+        }
+
+        let intervals = CleanupIntervals::new(std::time::Duration::from_millis(10))
+            .with_table("sessions_slow", std::time::Duration::from_secs(3600));
+        assert_eq!(
+            std::time::Duration::from_millis(10),
+            intervals.for_table("sessions_default"),
+            "Tables without an interval use the default"
+        );
+        let tasks = expired[0]
+            .0
+            .spawn_cleanup_per_table(tables.map(str::to_string), &intervals);
+        assert_eq!(3, tasks.len(), "One task per table");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        for (store, session) in &expired {
+            let remaining = store.record_exists(&session.id).await;
+            assert_eq!(
+                store.session_table == "sessions_slow",
+                remaining,
+                "Only tables due for cleanup should be cleaned: {}",
+                store.session_table
+            );
+        }
     }
 
-    async fn delete(&self, session_id: &Id) -> Result<()> {
-        self.client
-            .delete::<Option<SessionRecord>>((&self.session_table, &session_id.to_string()))
+    #[tokio::test]
+    async fn json_format() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_serialization_format(SerializationFormat::Json);
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(session, loaded, "Loaded JSON session");
+
+        let data: Vec<String> = db
+            .query(format!("select value data from {SESSIONS_TABLE}"))
             .await
-            .map_err(|e| Error::Backend(e.to_string()))?;
+            .expect("Error selecting data")
+            .take(0)
+            .expect("Error taking data");
+        let [data] = data.as_slice() else {
+            panic!("Expected one session, got {data:?}");
+        };
+        let json: serde_json::Value = serde_json::from_str(data).expect("Data should be JSON");
+        assert_eq!(
+            Some(&to_value("value")),
+            json["data"].get("key"),
+            "Session data should be readable in the table"
+        );
 
-        Ok(())
+        let store = store.with_serialization_format(SerializationFormat::MessagePack);
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(
+            session, loaded,
+            "JSON session should load whatever the configured format"
+        );
     }
-}
-
-#[cfg(test)]
-mod test {
-    use std::collections::HashMap;
 
-    use tower_sessions::cookie::time::{Duration, OffsetDateTime};
+    #[tokio::test]
+    async fn native_data() {
+        let db = new_db_connection().await;
+        let store =
+            SurrealSessionStore::new_with_native_data(db.clone(), SESSIONS_TABLE.to_string());
+        let mut session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        session
+            .data
+            .insert("user_id".to_string(), serde_json::json!(42));
+        session.expiry_date = session
+            .expiry_date
+            .replace_nanosecond(0)
+            .expect("Error truncating expiry");
+        save_session(&store, &session).await;
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(session, loaded, "Loaded native session");
 
-    use super::*;
+        let ids: Vec<String> = db
+            .query(format!(
+                "select value record::id(id) from {SESSIONS_TABLE} where data.user_id = 42"
+            ))
+            .await
+            .expect("Error selecting sessions")
+            .take(0)
+            .expect("Error taking ids");
+        assert_eq!(
+            vec![session.id.to_string()],
+            ids,
+            "Session data should be queryable"
+        );
 
-    static SESSIONS_TABLE: &str = "sessions";
+        let store = store.with_serialization_format(SerializationFormat::MessagePack);
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(
+            session, loaded,
+            "Native session should load whatever the configured format"
+        );
+    }
 
-    type DB = surrealdb::engine::local::Db;
+    #[tokio::test]
+    async fn custom_expiry_column() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_expiry_column("session_expires_at".to_string())
+            .expect("Valid column");
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let expired = make_record(None, [].to_vec(), Duration::days(-1));
+        save_session(&store, &session).await;
+        save_session(&store, &expired).await;
 
-    async fn new_db_connection() -> Surreal<DB> {
-        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+        let expiries: Vec<i64> = db
+            .query(format!(
+                "select value session_expires_at from {SESSIONS_TABLE} where expiry_date = none"
+            ))
             .await
-            .expect("Surreal initialization failure");
-        db.use_ns("testing")
+            .expect("Error selecting sessions")
+            .take(0)
+            .expect("Error taking expiries");
+        assert_eq!(2, expiries.len(), "Expiry should be in the custom column");
+
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(session, loaded, "Loaded session");
+        assert!(
+            load_session(&store, &expired).await.is_none(),
+            "Expired session should not load"
+        );
+        store
+            .delete_expired()
             .await
-            .expect("Surreal namespace initialization failure");
-        db.use_db("testing")
+            .expect("Error deleting expired");
+        store.delete(&session.id).await.expect("Error deleting");
+        let remaining: Vec<IgnoredAny> = db
+            .select(SESSIONS_TABLE)
             .await
-            .expect("Surreal database initialization failure");
-        db
+            .expect("Error selecting sessions");
+        assert!(remaining.is_empty(), "Sessions should be deleted");
+
+        for invalid in ["", "expiry date", "expiry_date; remove table sessions"] {
+            assert!(
+                SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+                    .with_expiry_column(invalid.to_string())
+                    .is_err(),
+                "Column {invalid:?} should be rejected"
+            );
+        }
     }
 
     #[tokio::test]
-    async fn basic_roundtrip() {
+    async fn custom_field_names() {
         let db = new_db_connection().await;
-        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
-        let record = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
-        save_session(&store, &record).await;
-        let loaded = load_session(&store, &record).await.expect("Value missing");
-        assert_eq!(record, loaded, "Loaded value should equal original");
+        // Writing any other field fails in a schemafull table.
+        db.query(format!("define table {SESSIONS_TABLE} schemafull"))
+            .await
+            .expect("Error defining table");
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_data_column("session_blob".to_string())
+            .and_then(|store| store.with_expiry_column("session_expires_at".to_string()))
+            .expect("Valid columns");
+        store.define_table().await.expect("Error defining fields");
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let expired = make_record(None, [].to_vec(), Duration::days(-1));
+        save_session(&store, &session).await;
+        save_session(&store, &expired).await;
+
+        let blobs: Vec<Vec<u8>> = db
+            .query(format!(
+                "select value session_blob from {SESSIONS_TABLE} where session_expires_at > 0"
+            ))
+            .await
+            .expect("Error selecting sessions")
+            .take(0)
+            .expect("Error taking data");
+        assert_eq!(2, blobs.len(), "Data should be in the custom field");
+
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(session, loaded, "Loaded session");
+        assert!(
+            load_session(&store, &expired).await.is_none(),
+            "Expired session should not load"
+        );
+        assert_eq!(
+            1,
+            store
+                .delete_expired_count()
+                .await
+                .expect("Error deleting expired")
+        );
+        store.delete(&session.id).await.expect("Error deleting");
+        assert_eq!(0, store.count_all().await.expect("Error counting"));
+
+        assert!(
+            store
+                .with_data_column("data; remove table sessions".to_string())
+                .is_err(),
+            "Invalid data column should be rejected"
+        );
     }
 
     #[tokio::test]
-    async fn delete_expired() {
+    async fn datetime_expiry() {
         let db = new_db_connection().await;
-        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
-        let expired = make_record(None, [].to_vec(), Duration::ZERO);
-        let expired2 = make_record(None, [("key", "value")].to_vec(), Duration::days(-1));
-        let not_expired = make_record(None, [].to_vec(), Duration::days(1));
-        let not_expired2 = make_record(None, [("key", "value")].to_vec(), Duration::minutes(1));
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_datetime_expiry(true);
+        store.define_table().await.expect("Error defining table");
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        let expired = make_record(None, [].to_vec(), Duration::days(-1));
+        save_session(&store, &session).await;
+        save_session(&store, &expired).await;
 
-        for session in [&expired, &expired2, &not_expired, &not_expired2] {
-            save_session(&store, session).await;
-            select_session(&db, session)
+        let datetimes: Vec<bool> = db
+            .query(format!(
+                "select value type::is::datetime(expiry_date) from {SESSIONS_TABLE}"
+            ))
+            .await
+            .expect("Error selecting sessions")
+            .take(0)
+            .expect("Error taking expiries");
+        assert_eq!(vec![true, true], datetimes, "Expiry should be a datetime");
+
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(session, loaded, "Loaded session");
+        assert!(
+            load_session(&store, &expired).await.is_none(),
+            "Expired session should not load"
+        );
+        assert_eq!(
+            Some(session.expiry_date.unix_timestamp()),
+            store
+                .get_expiry(&session.id)
                 .await
-                .expect("Session should be in the database");
-        }
+                .expect("Error getting expiry")
+                .map(|expiry| expiry.unix_timestamp())
+        );
+        assert_eq!(1, store.count_active().await.expect("Error counting"));
 
+        let expiry_date = session.expiry_date + Duration::days(1);
         store
-            .delete_expired()
+            .touch(&session.id, expiry_date)
             .await
-            .expect("Error deleting expired");
+            .expect("Error touching session");
+        let replaced = store
+            .replace_data(&session.id, HashMap::new())
+            .await
+            .expect("Error replacing data")
+            .expect("Session should be live");
+        assert_eq!(
+            expiry_date.unix_timestamp(),
+            replaced.expiry_date.unix_timestamp(),
+            "Touched expiry date"
+        );
 
-        for not_expired in [&not_expired, &not_expired2] {
-            select_session(&db, not_expired)
-                .await
-                .expect("Not-expired session should be in the database");
+        let deleted = store
+            .delete_expired_count()
+            .await
+            .expect("Error deleting expired");
+        assert_eq!(1, deleted, "Deleted expired session");
+        assert_eq!(1, store.count_all().await.expect("Error counting"));
+    }
 
-            let loaded = load_session(&store, not_expired)
-                .await
-                .expect("No session loaded");
+    #[tokio::test]
+    async fn count_sessions() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        assert_eq!(0, store.count_active().await.expect("Error counting"));
+        assert_eq!(0, store.count_all().await.expect("Error counting"));
 
-            assert_eq!(
-                not_expired, &loaded,
-                "Not-expired session should be loaded from the store",
-            );
+        for offset in [Duration::days(-1), Duration::ZERO, Duration::days(1)] {
+            save_session(&store, &make_record(None, [].to_vec(), offset)).await;
         }
-
-        for expired in [&expired, &expired2] {
-            let loaded = select_session(&db, expired).await;
-            assert!(
-                loaded.is_none(),
-                "Expired session should not be in the database"
-            );
-
-            let loaded = load_session(&store, expired).await;
-
-            assert!(
-                loaded.is_none(),
-                "Expired session should not be loaded from the store",
-            );
+        for offset in [Duration::minutes(1), Duration::days(2)] {
+            save_session(
+                &store,
+                &make_record(None, [("key", "value")].to_vec(), offset),
+            )
+            .await;
         }
+        assert_eq!(
+            3,
+            store.count_active().await.expect("Error counting"),
+            "Active sessions"
+        );
+        assert_eq!(
+            5,
+            store.count_all().await.expect("Error counting"),
+            "All sessions"
+        );
     }
 
     #[tokio::test]
@@ -313,7 +7702,327 @@ mod test {
         assert_ne!(session.id, loaded.id, "Loaded session");
     }
 
-    fn make_record(id: Option<Id>, values: Vec<(&str, &str)>, date_offset: Duration) -> Record {
+    #[tokio::test]
+    async fn create_strict() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let session = make_record(None, [("key", "first")].to_vec(), Duration::hours(1));
+        store
+            .create_strict(&session)
+            .await
+            .expect("Error creating session");
+        assert_eq!(
+            Some(session.clone()),
+            load_session(&store, &session).await,
+            "Session should be created under its own id"
+        );
+
+        let conflicting = make_record(
+            Some(session.id),
+            [("key", "second")].to_vec(),
+            Duration::hours(2),
+        );
+        let error = store
+            .create_strict(&conflicting)
+            .await
+            .expect_err("Taken id should fail");
+        assert!(
+            matches!(&error, SurrealStoreError::AlreadyExists(Error::Backend(message))
+                if message.starts_with(SESSION_EXISTS)),
+            "Should be an already exists error: {error}"
+        );
+        assert_eq!(
+            Some(session.clone()),
+            load_session(&store, &session).await,
+            "The existing session should be unchanged"
+        );
+        assert_eq!(1, store.count_all().await.expect("Error counting"));
+    }
+
+    #[tokio::test]
+    async fn create_with_id_generator() {
+        let db = new_db_connection().await;
+        let taken = make_record(None, [].to_vec(), Duration::hours(1));
+        let free = Id(taken.id.0.wrapping_add(1));
+        let generated = Arc::new(Mutex::new(vec![free, taken.id]));
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string())
+            .with_id_generator({
+                let generated = Arc::clone(&generated);
+                move || {
+                    generated
+                        .lock()
+                        .unwrap()
+                        .pop()
+                        .expect("Generator called too often")
+                }
+            });
+        save_session(&store, &taken).await;
+
+        // The first generated id collides again, the second is free.
+        let mut session = make_record(Some(taken.id), [].to_vec(), Duration::hours(2));
+        create_session(&store, &mut session).await;
+        assert_eq!(free, session.id, "Session should get the free generated id");
+        assert!(
+            generated.lock().unwrap().is_empty(),
+            "Generator called twice"
+        );
+        assert_eq!(Some(session.clone()), load_session(&store, &session).await);
+        assert_eq!(Some(taken.clone()), load_session(&store, &taken).await);
+    }
+
+    #[tokio::test]
+    async fn create_concurrently() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let id = Id::default();
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let store = store.clone();
+                let mut session = make_record(Some(id), [].to_vec(), Duration::hours(1));
+                tokio::spawn(async move {
+                    create_session(&store, &mut session).await;
+                    session.id
+                })
+            })
+            .collect();
+
+        let mut ids = HashSet::new();
+        for task in tasks {
+            ids.insert(task.await.expect("Create task failed"));
+        }
+        assert_eq!(20, ids.len(), "Created sessions should have unique ids");
+        assert_eq!(20, store.count_all().await.expect("Error counting"));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn encrypted() {
+        let db = new_db_connection().await;
+        let store =
+            SurrealSessionStore::new_encrypted(db.clone(), SESSIONS_TABLE.to_string(), [7; 32]);
+        let session = make_record(None, [("key", "secret value")].to_vec(), Duration::days(1));
+        save_session(&store, &session).await;
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(session, loaded, "Loaded encrypted session");
+
+        let stored = select_session(&db, &session)
+            .await
+            .expect("Session should be in the database");
+        let plaintext = make_session_record(&session).await;
+        assert_eq!(
+            plaintext.expiry_date, stored.expiry_date,
+            "Expiry date should be stored in the clear"
+        );
+        assert_ne!(plaintext.data, stored.data, "Data should be encrypted");
+        let stored_bytes = stored.data.as_bytes();
+        assert!(
+            !stored_bytes
+                .windows(b"secret value".len())
+                .any(|window| window == b"secret value"),
+            "Stored data should not contain the plaintext"
+        );
+
+        save_session(&store, &session).await;
+        let resaved = select_session(&db, &session)
+            .await
+            .expect("Session should be in the database");
+        assert_ne!(
+            stored.data, resaved.data,
+            "Each save should use a new nonce"
+        );
+
+        let other_key =
+            SurrealSessionStore::new_encrypted(db.clone(), SESSIONS_TABLE.to_string(), [8; 32]);
+        assert!(
+            other_key.load(&session.id).await.is_err(),
+            "Loading with another key should fail"
+        );
+        let unencrypted = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        save_session(&unencrypted, &session).await;
+        assert!(
+            store.load(&session.id).await.is_err(),
+            "Loading an unencrypted session should fail"
+        );
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn encrypted_swapped() {
+        let db = new_db_connection().await;
+        let store =
+            SurrealSessionStore::new_encrypted(db.clone(), SESSIONS_TABLE.to_string(), [7; 32]);
+        let alice = make_record(None, [("user_id", "alice")].to_vec(), Duration::days(1));
+        let bob = make_record(None, [("user_id", "bob")].to_vec(), Duration::days(1));
+        save_session(&store, &alice).await;
+        save_session(&store, &bob).await;
+
+        let stored = select_session(&db, &alice)
+            .await
+            .expect("Session should be in the database");
+        let _: Option<SessionRecord> = db
+            .upsert((SESSIONS_TABLE, bob.id.to_string()))
+            .content(stored)
+            .await
+            .expect("Error writing record");
+        store
+            .load(&bob.id)
+            .await
+            .expect_err("Data copied from another session should fail to decrypt");
+        let loaded = load_session(&store, &alice).await.expect("No session");
+        assert_eq!(alice, loaded, "Original session should still load");
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn compressed() {
+        let db = new_db_connection().await;
+        let store = SurrealSessionStore::new(db.clone(), SESSIONS_TABLE.to_string());
+        let compressing = store.clone().with_compression(Some(6));
+        let value = "blob ".repeat(1000);
+        let session = make_record(None, [("key", value.as_str())].to_vec(), Duration::days(1));
+        save_session(&compressing, &session).await;
+        let loaded = load_session(&compressing, &session)
+            .await
+            .expect("No session");
+        assert_eq!(session, loaded, "Loaded compressed session");
+
+        let stored = select_session(&db, &session)
+            .await
+            .expect("Session should be in the database");
+        let uncompressed = make_session_record(&session).await;
+        assert!(
+            stored.data.len() < uncompressed.data.len() / 10,
+            "Data should be compressed: {} bytes",
+            stored.data.len()
+        );
+        let loaded = load_session(&store, &session).await.expect("No session");
+        assert_eq!(
+            session, loaded,
+            "Compressed session should load without compression enabled"
+        );
+        let limited = compressing
+            .clone()
+            .with_max_decode_size(Some(uncompressed.data.len() - 1));
+        assert!(
+            limited.load(&session.id).await.is_err(),
+            "Decompressed size should be limited"
+        );
+
+        save_session(&store, &session).await;
+        let loaded = load_session(&compressing, &session)
+            .await
+            .expect("No session");
+        assert_eq!(session, loaded, "Uncompressed session should still load");
+
+        let json = compressing.with_serialization_format(SerializationFormat::Json);
+        save_session(&json, &session).await;
+        let loaded = load_session(&json, &session).await.expect("No session");
+        assert_eq!(session, loaded, "Loaded compressed JSON session");
+    }
+
+    #[cfg(feature = "env")]
+    #[tokio::test]
+    async fn from_env_vars() {
+        let vars = HashMap::from([
+            (ENV_URL, "mem://"),
+            (ENV_NAMESPACE, "testing"),
+            (ENV_DATABASE, "testing"),
+            (ENV_SESSION_TABLE, "env_sessions"),
+        ]);
+        let store = SurrealSessionStore::from_vars(|name| vars.get(name).map(|v| v.to_string()))
+            .await
+            .expect("Error creating store from environment");
+        assert_eq!("env_sessions", store.session_table);
+
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        store.save(&session).await.expect("Error saving session");
+        let loaded = store
+            .load(&session.id)
+            .await
+            .expect("Error loading session");
+        assert_eq!(Some(session), loaded, "Loaded session");
+    }
+
+    #[cfg(feature = "env")]
+    #[tokio::test]
+    async fn from_env_vars_missing() {
+        let vars = HashMap::from([(ENV_URL, "mem://"), (ENV_NAMESPACE, "testing")]);
+        let error = SurrealSessionStore::from_vars(|name| vars.get(name).map(|v| v.to_string()))
+            .await
+            .expect_err("Store should not be created with a missing variable");
+        assert!(
+            error.to_string().contains(ENV_DATABASE),
+            "Error should name the missing variable: {error}"
+        );
+    }
+
+    #[cfg(feature = "remote")]
+    #[tokio::test]
+    async fn connect_remote_errors() {
+        let error = SurrealSessionStore::connect_remote("mem://", "testing", "testing", None)
+            .await
+            .expect_err("Local engines should be rejected");
+        assert!(
+            matches!(error, SurrealStoreError::InvalidConfiguration(_)),
+            "Should be an invalid configuration error: {error}"
+        );
+
+        // Nothing listens on port 1.
+        let error =
+            SurrealSessionStore::connect_remote("ws://127.0.0.1:1", "testing", "testing", None)
+                .await
+                .expect_err("Unreachable server should fail");
+        assert!(
+            matches!(&error, SurrealStoreError::Connection(_))
+                && error.to_string().contains("ws://127.0.0.1:1"),
+            "Should be a connection error naming the URL: {error}"
+        );
+    }
+
+    /// Connect to the server of the `test-remote` tests, started with
+    /// `surreal start --user root --pass root memory`.
+    #[cfg(feature = "test-remote")]
+    #[tokio::test]
+    async fn connect_remote() {
+        let url = std::env::var("SURREALDB_TEST_URL")
+            .unwrap_or_else(|_| "ws://127.0.0.1:8000".to_string());
+        let credentials = Credentials::Root {
+            username: "root".to_string(),
+            password: "root".to_string(),
+        };
+        let store =
+            SurrealSessionStore::connect_remote(&url, "testing", "testing", Some(credentials))
+                .await
+                .expect("Error connecting");
+        let store = store
+            .for_table("remote_sessions")
+            .expect("Valid table name");
+        let session = make_record(None, [("key", "value")].to_vec(), Duration::days(1));
+        store.save(&session).await.expect("Error saving");
+        assert_eq!(
+            Some(session.clone()),
+            store.load(&session.id).await.expect("Error loading")
+        );
+        store.delete(&session.id).await.expect("Error deleting");
+
+        let wrong = Credentials::Root {
+            username: "root".to_string(),
+            password: "wrong".to_string(),
+        };
+        let error = SurrealSessionStore::connect_remote(&url, "testing", "testing", Some(wrong))
+            .await
+            .expect_err("Wrong credentials should fail");
+        assert!(
+            error.to_string().contains("Signing in"),
+            "Error should name the failed step: {error}"
+        );
+    }
+
+    pub(crate) fn make_record(
+        id: Option<Id>,
+        values: Vec<(&str, &str)>,
+        date_offset: Duration,
+    ) -> Record {
         Record {
             id: id.unwrap_or_default(),
             data: HashMap::from_iter(values.iter().map(|(k, v)| (k.to_string(), to_value(v)))),
@@ -328,10 +8037,37 @@ mod test {
     }
 
     async fn make_session_record(session: &Record) -> SessionRecord {
-        SessionRecord::from_session(session).expect("Error deserializing")
+        SessionRecord::from_session(
+            session,
+            SerializationFormat::default(),
+            MessagePackEncoding::default(),
+            None,
+        )
+        .expect("Error deserializing")
+    }
+
+    impl SurrealSessionStore<DB> {
+        /// Whether the record of a session exists, whether or not it
+        /// has expired.
+        async fn record_exists(&self, session_id: &Id) -> bool {
+            let record: Option<SessionRecord> = self
+                .client
+                .select((self.session_table.clone(), self.record_key(session_id)))
+                .await
+                .expect("Error selecting session");
+            record.is_some()
+        }
+    }
+
+    async fn upsert_raw(db: &Surreal<DB>, key: &str, session: &Record) {
+        let _: Option<SessionRecord> = db
+            .upsert((SESSIONS_TABLE, key))
+            .content(make_session_record(session).await)
+            .await
+            .expect("Error writing session record");
     }
 
-    async fn save_session(store: &SurrealSessionStore<DB>, session: &Record) {
+    pub(crate) async fn save_session(store: &SurrealSessionStore<DB>, session: &Record) {
         store.save(session).await.expect("Error saving session")
     }
 
@@ -339,7 +8075,10 @@ mod test {
         store.create(session).await.expect("Error creating session")
     }
 
-    async fn load_session(store: &SurrealSessionStore<DB>, session: &Record) -> Option<Record> {
+    pub(crate) async fn load_session(
+        store: &SurrealSessionStore<DB>,
+        session: &Record,
+    ) -> Option<Record> {
         store
             .load(&session.id)
             .await
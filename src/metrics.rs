@@ -0,0 +1,34 @@
+//! Hooks reporting metrics of session store operations.
+use std::time::Duration;
+
+/// Receiver of metrics of the operations of a
+/// [`SurrealSessionStore`](crate::SurrealSessionStore), see
+/// [`with_metrics`](crate::SurrealSessionStore::with_metrics).
+/// Implement it to forward the metrics to e.g. the `metrics` or
+/// `prometheus` crates. Every method does nothing by default, so only
+/// the metrics of interest need implementing.
+///
+/// The methods are called on the task running the operation, after it
+/// completed, so they should return quickly.
+pub trait SessionMetrics: Send + Sync {
+    /// A session was created.
+    fn session_created(&self) {}
+
+    /// A session was saved.
+    fn session_saved(&self) {}
+
+    /// A session was loaded, and `_hit` is whether a live session was
+    /// found.
+    fn session_loaded(&self, _hit: bool) {}
+
+    /// A session was deleted.
+    fn session_deleted(&self) {}
+
+    /// A cleanup deleted `_count` expired sessions.
+    fn expired_deleted(&self, _count: u64) {}
+
+    /// An operation completed after `_elapsed`, successfully or not.
+    /// The operation is one of `create`, `save`, `load`, `delete` and
+    /// `delete_expired`, as in the names of tracing spans.
+    fn operation_completed(&self, _operation: &'static str, _elapsed: Duration, _success: bool) {}
+}
@@ -0,0 +1,81 @@
+//! OpenTelemetry conventions for spans and metrics of store operations,
+//! available with the `otel` feature.
+use std::time::Duration;
+
+use opentelemetry::{
+    metrics::{Histogram, Meter},
+    KeyValue,
+};
+use tower_sessions_core::session_store::Error;
+use tracing::Span;
+
+use crate::SessionMetrics;
+
+/// Value of the `db.system` attribute.
+const DB_SYSTEM: &str = "surrealdb";
+
+/// Record the attributes of the OpenTelemetry database client
+/// conventions on the `span` of `operation` on `table`, which an
+/// OpenTelemetry layer such as `tracing-opentelemetry` exports as span
+/// attributes. The span is named `{operation} {table}` as the
+/// conventions recommend.
+pub(crate) fn annotate(span: &Span, operation: &str, table: &str) {
+    span.record("otel.name", format!("{operation} {table}"));
+    span.record("otel.kind", "client");
+    span.record("db.system", DB_SYSTEM);
+    span.record("db.operation.name", operation);
+    span.record("db.collection.name", table);
+}
+
+/// Mark `span` as failed with `error`.
+pub(crate) fn record_error(span: &Span, error: &Error) {
+    span.record("otel.status_code", "ERROR");
+    span.record("error.type", error_type(error));
+}
+
+/// Value of the `error.type` attribute of `error`.
+fn error_type(error: &Error) -> &'static str {
+    match error {
+        Error::Encode(_) => "encode",
+        Error::Decode(_) => "decode",
+        Error::Backend(_) => "backend",
+    }
+}
+
+/// [`SessionMetrics`] recording the duration of store operations in
+/// the `db.client.operation.duration` histogram of the OpenTelemetry
+/// database client conventions, in seconds, with the `db.system` and
+/// `db.operation.name` attributes, and `error.type` for failed
+/// operations. Configure it with
+/// [`with_metrics`](crate::SurrealSessionStore::with_metrics).
+#[derive(Debug, Clone)]
+pub struct OtelMetrics {
+    duration: Histogram<f64>,
+}
+
+impl OtelMetrics {
+    /// Record metrics with instruments created from `meter`.
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            duration: meter
+                .f64_histogram("db.client.operation.duration")
+                .with_unit("s")
+                .with_description("Duration of session store operations")
+                .init(),
+        }
+    }
+}
+
+impl SessionMetrics for OtelMetrics {
+    fn operation_completed(&self, operation: &'static str, elapsed: Duration, success: bool) {
+        let mut attributes = vec![
+            KeyValue::new("db.system", DB_SYSTEM),
+            KeyValue::new("db.operation.name", operation),
+        ];
+        if !success {
+            // The error itself isn't passed to metrics.
+            attributes.push(KeyValue::new("error.type", "_OTHER"));
+        }
+        self.duration.record(elapsed.as_secs_f64(), &attributes);
+    }
+}
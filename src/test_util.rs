@@ -0,0 +1,429 @@
+//! Utilities for testing code that uses SurrealDB, available with the
+//! `test-util` feature.
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use surrealdb::{Connection, Surreal};
+use time::{Duration, OffsetDateTime};
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store::Result,
+    ExpiredDeletion, SessionStore,
+};
+
+use crate::{backend_error, write_error, KeyedSessionRecord, StoreResult, SurrealSessionStore};
+
+static NEXT_DATABASE: AtomicUsize = AtomicUsize::new(0);
+
+/// A uniquely named namespace and database, so tests sharing a
+/// SurrealDB server don't see each other's data.
+///
+/// Creating one switches the given client to the new namespace and
+/// database. Call [`remove`](Self::remove) at the end of the test to
+/// delete the namespace; if it is dropped instead, removal is attempted
+/// in the background when a Tokio runtime is available.
+#[derive(Debug)]
+pub struct TemporaryDatabase<DB: std::fmt::Debug + Connection> {
+    client: Surreal<DB>,
+    namespace: String,
+    database: String,
+    removed: bool,
+}
+
+impl<DB: std::fmt::Debug + Connection> TemporaryDatabase<DB> {
+    /// Switch `client` to a new, uniquely named namespace and database.
+    pub async fn new(client: Surreal<DB>) -> StoreResult<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let id = format!(
+            "{}_{nanos}_{}",
+            std::process::id(),
+            NEXT_DATABASE.fetch_add(1, Ordering::Relaxed)
+        );
+        let namespace = format!("test_ns_{id}");
+        let database = format!("test_db_{id}");
+        client
+            .use_ns(&namespace)
+            .use_db(&database)
+            .await
+            .map_err(backend_error)?;
+        Ok(Self {
+            client,
+            namespace,
+            database,
+            removed: false,
+        })
+    }
+
+    /// The client, using the temporary namespace and database.
+    pub fn client(&self) -> &Surreal<DB> {
+        &self.client
+    }
+
+    /// Name of the temporary namespace.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Name of the temporary database.
+    pub fn database(&self) -> &str {
+        &self.database
+    }
+
+    /// Remove the temporary namespace along with everything in it.
+    pub async fn remove(mut self) -> StoreResult<()> {
+        self.removed = true;
+        remove_namespace(&self.client, &self.namespace).await
+    }
+}
+
+impl<DB: std::fmt::Debug + Connection> Drop for TemporaryDatabase<DB> {
+    fn drop(&mut self) {
+        if self.removed {
+            return;
+        }
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let client = self.client.clone();
+            let namespace = std::mem::take(&mut self.namespace);
+            runtime.spawn(async move {
+                let _ = remove_namespace(&client, &namespace).await;
+            });
+        }
+    }
+}
+
+/// An in-memory [`SessionStore`] with the same behavior as
+/// [`SurrealSessionStore`], for testing code that uses sessions without
+/// a SurrealDB server. Creating a session moves it to a new id while its
+/// id is taken, sessions expire to the second like in the database,
+/// expired sessions don't load, and deleting a missing session succeeds.
+///
+/// Meant for tests only: sessions are lost when the store is dropped and
+/// are not shared between processes. Clones share their sessions.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySessionStore {
+    sessions: Arc<Mutex<HashMap<Id, Record>>>,
+}
+
+impl MemorySessionStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<Id, Record>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Whether a session is live, comparing to the second like the queries
+/// of [`SurrealSessionStore`].
+fn is_live(session: &Record) -> bool {
+    session.expiry_date.unix_timestamp() > OffsetDateTime::now_utc().unix_timestamp()
+}
+
+#[async_trait]
+impl ExpiredDeletion for MemorySessionStore {
+    async fn delete_expired(&self) -> Result<()> {
+        self.sessions().retain(|_, session| is_live(session));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn create(&self, session: &mut Record) -> Result<()> {
+        let mut sessions = self.sessions();
+        while sessions.contains_key(&session.id) {
+            session.id = Id::default();
+        }
+        sessions.insert(session.id, session.clone());
+        Ok(())
+    }
+
+    async fn save(&self, session: &Record) -> Result<()> {
+        self.sessions().insert(session.id, session.clone());
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Record>> {
+        Ok(self
+            .sessions()
+            .get(session_id)
+            .filter(|session| is_live(session))
+            .cloned())
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<()> {
+        self.sessions().remove(session_id);
+        Ok(())
+    }
+}
+
+/// A session with a new id holding `data` and expiring `expires_in` from
+/// now, or already expired if it is negative. Meant for seeding sessions
+/// in tests without computing expiry dates by hand.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use time::Duration;
+/// use tower_sessions_core::SessionStore;
+/// use tower_sessions_surrealdb_store::{test_util::record_expiring_in, SurrealSessionStore};
+///
+/// let db = surrealdb::Surreal::new::<surrealdb::engine::local::Mem>(()).await?;
+/// db.use_ns("testing").use_db("testing").await?;
+/// let store = SurrealSessionStore::new(db, "sessions".to_string());
+///
+/// let session = record_expiring_in(Duration::hours(1), [("user", "alice")]);
+/// store.save(&session).await?;
+/// assert_eq!(Some(session.clone()), store.load(&session.id).await?);
+///
+/// let expired = record_expiring_in(Duration::hours(-1), [("user", "bob")]);
+/// store.save(&expired).await?;
+/// assert_eq!(None, store.load(&expired.id).await?);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// # }).unwrap();
+/// ```
+pub fn record_expiring_in<K, V>(
+    expires_in: Duration,
+    data: impl IntoIterator<Item = (K, V)>,
+) -> Record
+where
+    K: Into<String>,
+    V: Into<serde_json::Value>,
+{
+    Record {
+        id: Id::default(),
+        data: data
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect(),
+        expiry_date: OffsetDateTime::now_utc() + expires_in,
+    }
+}
+
+async fn remove_namespace<DB: Connection>(
+    client: &Surreal<DB>,
+    namespace: &str,
+) -> StoreResult<()> {
+    // The name is generated by `TemporaryDatabase::new` so is safe to
+    // interpolate.
+    client
+        .query(format!("remove namespace if exists {namespace}"))
+        .await
+        .map_err(backend_error)?
+        .check()
+        .map_err(backend_error)?;
+    Ok(())
+}
+
+impl<DB: std::fmt::Debug + Connection> SurrealSessionStore<DB> {
+    /// Write the given sessions, with their ids and expiry dates as is,
+    /// in a single transaction. Meant for setting up known sessions in
+    /// tests rather than calling [`save`](tower_sessions_core::SessionStore::save)
+    /// for each.
+    pub async fn seed(&self, records: Vec<Record>) -> StoreResult<()> {
+        let mut rows = Vec::with_capacity(records.len());
+        for session in &records {
+            rows.push(KeyedSessionRecord {
+                key: self.record_key(&session.id),
+                record: self.encode(session).await?,
+            });
+        }
+        self.query(format!(
+            "begin transaction;
+for $row in $rows {{
+    upsert type::thing($table, $row.key)
+    content {{ {}: $row.data, {}: {} }};
+}};
+commit transaction;",
+            self.data_column,
+            self.expiry_column,
+            self.expiry_at("$row.expiry_date")
+        ))
+        .bind(("table", self.session_table.clone()))
+        .bind(("rows", rows))
+        .await
+        .map_err(write_error)?
+        .check()
+        .map_err(backend_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use serde::{de::IgnoredAny, Deserialize};
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct RootInfo {
+        namespaces: BTreeMap<String, IgnoredAny>,
+    }
+
+    async fn namespaces(db: &Surreal<surrealdb::engine::local::Db>) -> Vec<String> {
+        let info: Option<RootInfo> = db
+            .query("info for root")
+            .await
+            .expect("Error getting root info")
+            .take(0)
+            .expect("Error taking root info");
+        info.expect("No root info").namespaces.into_keys().collect()
+    }
+
+    #[tokio::test]
+    async fn temporary_database() {
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        let first = TemporaryDatabase::new(db.clone())
+            .await
+            .expect("Error creating temporary database");
+        first
+            .client()
+            .query("create item:1")
+            .await
+            .expect("Error creating item");
+        let first_namespace = first.namespace().to_string();
+
+        let second = TemporaryDatabase::new(db.clone())
+            .await
+            .expect("Error creating temporary database");
+        assert_ne!(first_namespace, second.namespace(), "Namespaces are unique");
+        assert_ne!(first.database(), second.database(), "Databases are unique");
+        assert!(
+            namespaces(&db).await.contains(&first_namespace),
+            "Namespace should exist"
+        );
+
+        first
+            .remove()
+            .await
+            .expect("Error removing temporary database");
+        assert!(
+            !namespaces(&db).await.contains(&first_namespace),
+            "Namespace should be removed"
+        );
+        second
+            .remove()
+            .await
+            .expect("Error removing temporary database");
+    }
+
+    #[tokio::test]
+    async fn seed() {
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        let database = TemporaryDatabase::new(db)
+            .await
+            .expect("Error creating temporary database");
+        let store = SurrealSessionStore::new(database.client().clone(), "sessions".to_string());
+        let records: Vec<Record> = [Duration::hours(1), Duration::hours(2), Duration::hours(-1)]
+            .into_iter()
+            .map(|offset| record_expiring_in(offset, [("key", "value")]))
+            .collect();
+
+        store.seed(records.clone()).await.expect("Error seeding");
+
+        for record in &records[..2] {
+            let loaded = store.load(&record.id).await.expect("Error loading");
+            assert_eq!(Some(record), loaded.as_ref(), "Seeded session");
+        }
+        let expired = store.load(&records[2].id).await.expect("Error loading");
+        assert!(expired.is_none(), "Seeded expired session");
+        database
+            .remove()
+            .await
+            .expect("Error removing temporary database");
+    }
+
+    /// Run the core operations on `store`, returning what was observed
+    /// along the way.
+    async fn core_operations(store: &(impl SessionStore + ExpiredDeletion)) -> Vec<bool> {
+        let make_record = |offset| record_expiring_in(offset, [("key", "value")]);
+
+        let live = make_record(Duration::hours(1));
+        let expired = make_record(Duration::hours(-1));
+        let mut duplicate = Record {
+            id: live.id,
+            ..make_record(Duration::hours(2))
+        };
+        let load = |session_id| async move {
+            store
+                .load(&session_id)
+                .await
+                .expect("Error loading session")
+        };
+
+        store.save(&live).await.expect("Error saving session");
+        store.save(&expired).await.expect("Error saving session");
+        store
+            .create(&mut duplicate)
+            .await
+            .expect("Error creating session");
+        let mut observed = vec![
+            duplicate.id != live.id,
+            load(live.id).await == Some(live.clone()),
+            load(duplicate.id).await == Some(duplicate.clone()),
+            load(expired.id).await.is_none(),
+        ];
+
+        store
+            .delete_expired()
+            .await
+            .expect("Error deleting expired");
+        store
+            .delete(&live.id)
+            .await
+            .expect("Error deleting session");
+        store
+            .delete(&Id::default())
+            .await
+            .expect("Error deleting missing session");
+        observed.extend([
+            load(live.id).await.is_none(),
+            load(duplicate.id).await.is_some(),
+        ]);
+        observed
+    }
+
+    #[tokio::test]
+    async fn memory_store_matches_surreal_store() {
+        let db = Surreal::new::<surrealdb::engine::local::Mem>(())
+            .await
+            .expect("Surreal initialization failure");
+        let database = TemporaryDatabase::new(db)
+            .await
+            .expect("Error creating temporary database");
+        let surreal_store =
+            SurrealSessionStore::new(database.client().clone(), "sessions".to_string());
+
+        let expected = core_operations(&surreal_store).await;
+        assert!(
+            expected.iter().all(|observed| *observed),
+            "Surreal store behavior: {expected:?}"
+        );
+        assert_eq!(
+            expected,
+            core_operations(&MemorySessionStore::new()).await,
+            "Memory store should behave like the Surreal store"
+        );
+        database
+            .remove()
+            .await
+            .expect("Error removing temporary database");
+    }
+}